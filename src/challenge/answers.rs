@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, path};
+
+pub const DEFAULT_ANSWERS: &str = "answers.toml";
+
+#[derive(Debug, Default, Deserialize)]
+struct Expected {
+    part1: Option<String>,
+    part2: Option<String>,
+}
+
+/// Known answers, looked up by day, input name and part
+///
+/// ```toml
+/// [day8.day8_ex]
+/// part1 = "5"
+/// part2 = "8"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Answers {
+    days: BTreeMap<String, BTreeMap<String, Expected>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Pass,
+    Fail { expected: String },
+    Unknown,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Pass => write!(f, "PASS"),
+            Verdict::Fail { expected } => write!(f, "FAIL (expected {})", expected),
+            Verdict::Unknown => write!(f, "UNKNOWN"),
+        }
+    }
+}
+
+impl Answers {
+    pub fn parse(s: &str) -> Result<Answers> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// Load the answers file, a missing file is treated as having no known answers
+    pub fn load<P: AsRef<path::Path>>(path: P) -> Result<Answers> {
+        let path = path.as_ref();
+        if !path.exists() {
+            log::warn!("answers file {} does not exist", path.display());
+            return Ok(Answers::default());
+        }
+        let text = super::read_to_string(path)?;
+        Answers::parse(&text)
+            .with_context(|| format!("could not parse answers file: {}", path.display()))
+    }
+
+    pub fn expected(&self, day: u32, part: u32, input_name: &str) -> Option<&str> {
+        let expected = self.days.get(&format!("day{}", day))?.get(input_name)?;
+        match part {
+            1 => expected.part1.as_deref(),
+            2 => expected.part2.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn check(&self, day: u32, part: u32, input_name: Option<&str>, answer: &str) -> Verdict {
+        match input_name.and_then(|name| self.expected(day, part, name)) {
+            Some(expected) if expected == answer => Verdict::Pass,
+            Some(expected) => Verdict::Fail {
+                expected: expected.to_string(),
+            },
+            None => Verdict::Unknown,
+        }
+    }
+}
+
+/// The name used to look up answers for an input file, `input/day8_ex` is `day8_ex`
+pub fn input_name<P: AsRef<path::Path>>(path: P) -> Option<String> {
+    path.as_ref()
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const ANSWERS: &str = include_str!("../../answers.toml");
+
+    #[test]
+    fn check_verdicts() {
+        let answers = Answers::parse(ANSWERS).unwrap();
+        assert_eq!(answers.check(8, 1, Some("day8_ex"), "5"), Verdict::Pass);
+        assert_eq!(
+            answers.check(8, 2, Some("day8_ex"), "7"),
+            Verdict::Fail {
+                expected: "8".to_string()
+            }
+        );
+        assert_eq!(answers.check(8, 2, Some("other"), "8"), Verdict::Unknown);
+        assert_eq!(answers.check(8, 2, None, "8"), Verdict::Unknown);
+        assert_eq!(answers.check(16, 2, Some("day16"), "0"), Verdict::Unknown);
+    }
+
+    #[test]
+    fn lookup_by_input_name() {
+        let answers = Answers::parse(ANSWERS).unwrap();
+        assert_eq!(answers.expected(13, 2, "day13"), Some("530015546283687"));
+        assert_eq!(input_name("input/day4_ex_valid").unwrap(), "day4_ex_valid");
+    }
+}
@@ -0,0 +1,306 @@
+use super::{runner::format_change, Solver};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_BASELINE: &str = "target/bench_baseline.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+    pub stddev: Duration,
+}
+
+impl Stats {
+    pub fn from_samples(samples: &[Duration]) -> Option<Stats> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let n = sorted.len();
+        let median = (sorted[(n - 1) / 2] + sorted[n / 2]) / 2;
+        let mean_secs = sorted.iter().map(|d| d.as_secs_f64()).sum::<f64>() / n as f64;
+        let variance = sorted
+            .iter()
+            .map(|d| (d.as_secs_f64() - mean_secs).powi(2))
+            .sum::<f64>()
+            / n as f64;
+        Some(Stats {
+            min: sorted[0],
+            median,
+            mean: Duration::from_secs_f64(mean_secs),
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>12.3} {:>12.3} {:>12.3} {:>12.3}",
+            ms(self.min),
+            ms(self.median),
+            ms(self.mean),
+            ms(self.stddev)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct BenchReport {
+    pub day: u32,
+    pub part: u32,
+    pub iterations: usize,
+    pub parse: Stats,
+    pub solve: Stats,
+    pub total: Stats,
+    /// Iterations where parsing on its own took longer than the whole part, so their solve
+    /// time is counted as 0
+    pub clamped: usize,
+    /// Medians of an earlier run, to compare against
+    pub baseline: Option<Baseline>,
+}
+
+/// Median times of a run in ms
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub parse: f64,
+    pub solve: f64,
+    pub total: f64,
+}
+
+/// Baselines of every benchmarked part, stored as JSON between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Baselines {
+    parts: BTreeMap<String, Baseline>,
+}
+
+fn key(day: u32, part: u32) -> String {
+    format!("day{}.part{}", day, part)
+}
+
+fn from_ms(ms: f64) -> Duration {
+    Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+}
+
+impl Baselines {
+    /// Load stored baselines, a missing file has none
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Baselines> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Baselines::default());
+        }
+        let text = super::read_to_string(path)?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("could not parse baselines: {}", path.display()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|d| *d != Path::new("")) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("could not create {}", dir.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).expect("baselines are always serializable");
+        fs::write(path, text).with_context(|| format!("could not write {}", path.display()))
+    }
+
+    pub fn get(&self, day: u32, part: u32) -> Option<Baseline> {
+        self.parts.get(&key(day, part)).copied()
+    }
+
+    /// Replace the baseline of the report's part with its medians
+    pub fn update(&mut self, report: &BenchReport) {
+        self.parts.insert(
+            key(report.day, report.part),
+            Baseline {
+                parse: ms(report.parse.median),
+                solve: ms(report.solve.median),
+                total: ms(report.total.median),
+            },
+        );
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "day {} part {} ({} iterations, times in ms)",
+            self.day, self.part, self.iterations
+        )?;
+        writeln!(
+            f,
+            "{:<6} {:>12} {:>12} {:>12} {:>12}",
+            "", "min", "median", "mean", "stddev"
+        )?;
+        writeln!(f, "{:<6} {}", "parse", self.parse)?;
+        writeln!(f, "{:<6} {}", "solve", self.solve)?;
+        write!(f, "{:<6} {}", "total", self.total)?;
+        if self.clamped > 0 {
+            write!(
+                f,
+                "\nsolve counted as 0 in {} of {} iterations, parsing alone took longer than \
+                 the whole part",
+                self.clamped, self.iterations
+            )?;
+        }
+        if let Some(base) = &self.baseline {
+            write!(
+                f,
+                "\nmedian vs baseline: parse {}, solve {}, total {}",
+                format_change(self.parse.median, from_ms(base.parse)),
+                format_change(self.solve.median, from_ms(base.solve)),
+                format_change(self.total.median, from_ms(base.total))
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn time<T, F: FnOnce() -> Result<T>>(f: F) -> Result<Duration> {
+    let start = Instant::now();
+    f()?;
+    Ok(start.elapsed())
+}
+
+/// Part functions parse their own input, so the solve time of each iteration
+/// is the time of the whole part less the time it took to parse on its own.
+///
+/// The two are timed in separate runs, so when parsing took longer the solve time is counted
+/// as 0 and the report says how often that happened.
+pub fn bench(
+    solver: &dyn Solver,
+    part: u32,
+    input: &str,
+    warmup: usize,
+    iterations: usize,
+) -> Result<BenchReport> {
+    for _ in 0..warmup {
+        solver.solve(part, input).context("warmup")?;
+    }
+
+    let mut parse_samples = Vec::with_capacity(iterations);
+    let mut solve_samples = Vec::with_capacity(iterations);
+    let mut total_samples = Vec::with_capacity(iterations);
+    let mut clamped = 0;
+    for idx in 0..iterations {
+        let parse = time(|| solver.parse(input)).context("parse")?;
+        let total = time(|| solver.solve(part, input)).context("solve")?;
+        log::debug!("iteration {}: parse {:?} total {:?}", idx, parse, total);
+        parse_samples.push(parse);
+        let solve = total.checked_sub(parse);
+        if solve.is_none() {
+            clamped += 1;
+        }
+        solve_samples.push(solve.unwrap_or_default());
+        total_samples.push(total);
+    }
+
+    let stats = |samples: &[Duration]| {
+        Stats::from_samples(samples)
+            .ok_or_else(|| anyhow::anyhow!("bench needs at least one iteration"))
+    };
+
+    Ok(BenchReport {
+        day: solver.day(),
+        part,
+        iterations,
+        parse: stats(&parse_samples)?,
+        solve: stats(&solve_samples)?,
+        total: stats(&total_samples)?,
+        clamped,
+        baseline: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(xs: &[u64]) -> Vec<Duration> {
+        xs.iter().map(|x| Duration::from_millis(*x)).collect()
+    }
+
+    #[test]
+    fn stats_empty() {
+        assert_eq!(Stats::from_samples(&[]), None);
+    }
+
+    #[test]
+    fn stats_odd() {
+        let s = Stats::from_samples(&millis(&[9, 2, 4, 4, 5, 5, 7])).unwrap();
+        assert_eq!(s.min, Duration::from_millis(2));
+        assert_eq!(s.median, Duration::from_millis(5));
+        assert_eq!(s.mean.as_millis(), 5);
+        assert_eq!(s.stddev.as_millis(), 2);
+    }
+
+    #[test]
+    fn stats_even() {
+        let s = Stats::from_samples(&millis(&[4, 1, 3, 2])).unwrap();
+        assert_eq!(s.min, Duration::from_millis(1));
+        assert_eq!(s.median, Duration::from_micros(2500));
+    }
+
+    #[test]
+    fn compare_to_baseline() {
+        let stats = |ms| Stats::from_samples(&millis(&[ms])).unwrap();
+        let mut report = BenchReport {
+            day: 15,
+            part: 1,
+            iterations: 1,
+            parse: stats(2),
+            solve: stats(6),
+            total: stats(8),
+            clamped: 0,
+            baseline: None,
+        };
+        let mut baselines = Baselines::default();
+        assert_eq!(baselines.get(15, 1), None);
+        baselines.update(&report);
+
+        let path = std::env::temp_dir()
+            .join(format!("aoc-bench-{}", std::process::id()))
+            .join("baseline.json");
+        baselines.save(&path).unwrap();
+        let loaded = Baselines::load(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(
+            loaded.get(15, 1),
+            Some(Baseline {
+                parse: 2.0,
+                solve: 6.0,
+                total: 8.0
+            })
+        );
+
+        report.baseline = loaded.get(15, 1);
+        report.parse = stats(1);
+        report.solve = stats(9);
+        assert!(report
+            .to_string()
+            .ends_with("median vs baseline: parse -50.0%, solve +50.0%, total +0.0%"));
+
+        report.iterations = 5;
+        report.clamped = 2;
+        assert!(report.to_string().contains(
+            "\nsolve counted as 0 in 2 of 5 iterations, parsing alone took longer than the whole \
+             part\n"
+        ));
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::{anyhow as ah, Context, Result};
+use crate::parsing::parse_at;
+use anyhow::{anyhow as ah, Result};
 use std::fmt;
 
 const EXPENSE_TARGET: i64 = 2020;
@@ -12,65 +13,184 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     pt2_impl(expenses)
 }
 
-fn pt1_impl(mut input: Vec<i64>) -> Result<i64> {
-    input.sort();
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
+fn pt1_impl(input: Vec<i64>) -> Result<i64> {
     let (a, b) = find_expenses(input.as_slice(), EXPENSE_TARGET)
         .ok_or_else(|| ah!("could not find valid expenses"))?;
-    let r = a * b;
-    Ok(r)
+    a.checked_mul(b)
+        .ok_or_else(|| ah!("product of {} and {} overflows", a, b))
 }
 
-fn pt2_impl(mut input: Vec<i64>) -> Result<i64> {
-    input.sort();
-    find_triple_expenses(input.as_slice()).ok_or_else(|| ah!("could not find valid expenses"))
+fn pt2_impl(input: Vec<i64>) -> Result<i64> {
+    let (a, b, c) = find_triple_expenses(input.as_slice(), EXPENSE_TARGET)
+        .ok_or_else(|| ah!("could not find valid expenses"))?;
+    a.checked_mul(b)
+        .and_then(|ab| ab.checked_mul(c))
+        .ok_or_else(|| ah!("product of {}, {} and {} overflows", a, b, c))
 }
 
-fn find_triple_expenses(input: &[i64]) -> Option<i64> {
-    for (idx, e) in input.iter().enumerate() {
-        let target = EXPENSE_TARGET - *e;
-        if let Some((a, b)) = find_expenses(&input[idx..], target) {
-            return Some(a * b * e);
-        }
+/// Three different entries which add up to `target`, in the order they appear
+fn find_triple_expenses(input: &[i64], target: i64) -> Option<(i64, i64, i64)> {
+    match find_first_k_sum(input, 3, target)?.as_slice() {
+        [a, b, c] => Some((input[*a], input[*b], input[*c])),
+        _ => None,
     }
-    None
 }
 
+/// Two different entries which add up to `target`, in the order they appear
 fn find_expenses(input: &[i64], target: i64) -> Option<(i64, i64)> {
-    let mut iter_low = 0usize;
-    let mut iter_high = input.len() - 1;
-    while iter_low < iter_high {
-        let target_high = target - input[iter_low];
-        while input[iter_high] > target_high {
-            iter_high -= 1;
+    match find_first_k_sum(input, 2, target)?.as_slice() {
+        [a, b] => Some((input[*a], input[*b])),
+        _ => None,
+    }
+}
+
+/// Every set of `k` distinct indices whose values add up to `target`, with the indices of
+/// each set in ascending order
+pub fn find_k_sum(values: &[i64], k: usize, target: i64) -> Vec<Vec<usize>> {
+    let mut found = Vec::new();
+    k_sum(values, k, target, |ixs| {
+        found.push(ixs.to_vec());
+        true
+    });
+    found
+}
+
+pub fn find_first_k_sum(values: &[i64], k: usize, target: i64) -> Option<Vec<usize>> {
+    let mut found = None;
+    k_sum(values, k, target, |ixs| {
+        found = Some(ixs.to_vec());
+        false
+    });
+    found
+}
+
+/// Calls `f` with each set of indices until it returns false
+fn k_sum<F: FnMut(&[usize]) -> bool>(values: &[i64], k: usize, target: i64, mut f: F) {
+    let mut sorted = values
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(ix, v)| (v, ix))
+        .collect::<Vec<_>>();
+    sorted.sort_unstable();
+    let mut chosen = Vec::with_capacity(k);
+    k_sum_from(&sorted, k, i128::from(target), &mut chosen, &mut f);
+}
+
+/// Search `sorted[..]` for `k` more values adding up to `target`, returning false to stop.
+///
+/// Sums are `i128` so they can't overflow for any `k` which fits in memory.
+fn k_sum_from<F: FnMut(&[usize]) -> bool>(
+    sorted: &[(i64, usize)],
+    k: usize,
+    target: i128,
+    chosen: &mut Vec<usize>,
+    f: &mut F,
+) -> bool {
+    if k == 0 {
+        if target != 0 {
+            return true;
+        }
+        let mut ixs = chosen.clone();
+        ixs.sort_unstable();
+        return f(&ixs);
+    }
+    if sorted.len() < k {
+        return true;
+    }
+    if k == 1 {
+        let start = sorted.partition_point(|(v, _)| i128::from(*v) < target);
+        for (v, ix) in &sorted[start..] {
+            if i128::from(*v) != target {
+                break;
+            }
+            chosen.push(*ix);
+            let more = k_sum_from(&[], 0, 0, chosen, f);
+            chosen.pop();
+            if !more {
+                return false;
+            }
         }
-        if input[iter_high] == target_high {
-            return Some((input[iter_low], input[iter_high]));
+        return true;
+    }
+    let largest = sorted[sorted.len() - (k - 1)..]
+        .iter()
+        .map(|(v, _)| i128::from(*v))
+        .sum::<i128>();
+    for (pos, (v, ix)) in sorted[..=sorted.len() - k].iter().enumerate() {
+        let v = i128::from(*v);
+        let smallest = sorted[pos..pos + k]
+            .iter()
+            .map(|(v, _)| i128::from(*v))
+            .sum::<i128>();
+        if smallest > target {
+            break;
         }
-        let target_low = target - target_high;
-        while input[iter_low] < target_low {
-            iter_low += 1;
+        if v + largest < target {
+            continue;
         }
-        if input[iter_high] == target_high {
-            return Some((input[iter_low], input[iter_high]));
+        chosen.push(*ix);
+        let more = k_sum_from(&sorted[pos + 1..], k - 1, target - v, chosen, f);
+        chosen.pop();
+        if !more {
+            return false;
         }
-        iter_low += 1;
     }
-    None
+    true
+}
+
+/// Print the values, and their product, for sets of `k` expenses which add up to `target`
+pub fn report(input: &str, k: usize, target: i64, all: bool) -> Result<String> {
+    let expenses = parse(input)?;
+    let found = if all {
+        find_k_sum(&expenses, k, target)
+    } else {
+        find_first_k_sum(&expenses, k, target).into_iter().collect()
+    };
+    if found.is_empty() {
+        anyhow::bail!("no {} expenses add up to {}", k, target);
+    }
+    let lines = found
+        .iter()
+        .map(|ixs| {
+            let values = ixs.iter().map(|ix| expenses[*ix]).collect::<Vec<_>>();
+            let product = values
+                .iter()
+                .try_fold(1i64, |acc, v| acc.checked_mul(*v))
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "overflow".to_string());
+            let sum = values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" + ");
+            format!("{} = {}, product {}", sum, target, product)
+        })
+        .collect::<Vec<_>>();
+    Ok(lines.join("\n"))
 }
 
+/// Expenses separated by any whitespace, not only one per line
 fn parse(input: &str) -> Result<Vec<i64>> {
-    input
-        .split_ascii_whitespace()
-        .map(|x| {
-            x.parse::<i64>()
-                .with_context(|| format!("could not parse: {}", x))
+    let expenses = input
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, l)| {
+            l.split_ascii_whitespace()
+                .map(move |x| parse_at(l, x, "an expense").map_err(|e| e.on_line(idx + 1)))
         })
-        .collect::<Result<Vec<_>>>()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(expenses)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     const INPUT: &str = include_str!("../../../input/day1");
 
     #[test]
@@ -87,6 +207,151 @@ mod tests {
         assert_eq!(pt1_impl(input).unwrap(), 514579)
     }
     #[test]
+    fn expenses_edge_cases() {
+        assert_eq!(find_expenses(&[], 2020), None);
+        assert_eq!(find_expenses(&[2020], 2020), None);
+        assert_eq!(find_expenses(&[1010], 2020), None);
+        assert_eq!(find_expenses(&[1010, 1010], 2020), Some((1010, 1010)));
+        assert_eq!(find_expenses(&[3000, 5, -980], 2020), Some((3000, -980)));
+        assert_eq!(find_expenses(&[1, 2, 3], 2020), None);
+        assert_eq!(
+            find_expenses(&[i64::MIN, 1, i64::MAX], -1),
+            Some((i64::MIN, i64::MAX))
+        );
+        assert_eq!(find_expenses(&[i64::MIN, -1], i64::MAX), None);
+
+        assert_eq!(find_triple_expenses(&[], 2020), None);
+        assert_eq!(find_triple_expenses(&[1010, 1010], 2020), None);
+        assert_eq!(
+            find_triple_expenses(&[1000, 20, 1000], 2020),
+            Some((1000, 20, 1000))
+        );
+        // an entry can't be used twice
+        assert_eq!(find_triple_expenses(&[10, 2000], 2020), None);
+        assert!(pt1_impl(vec![1]).is_err());
+        assert!(pt1_impl(vec![i64::MAX, 2020 - i64::MAX]).is_err());
+    }
+    #[test]
+    fn k_sums() {
+        let ex = vec![1721, 979, 366, 299, 675, 1456];
+        assert_eq!(find_k_sum(&ex, 2, 2020), vec![vec![0, 3]]);
+        assert_eq!(find_first_k_sum(&ex, 3, 2020), Some(vec![1, 2, 4]));
+        assert_eq!(find_k_sum(&ex, 1, 366), vec![vec![2]]);
+        assert_eq!(find_k_sum(&ex, 0, 0), vec![Vec::<usize>::new()]);
+        assert_eq!(find_k_sum(&ex, 7, 2020), Vec::<Vec<usize>>::new());
+        assert_eq!(find_first_k_sum(&[], 2, 0), None);
+
+        let dups = vec![5, 5, 5, -5, 10];
+        assert_eq!(
+            find_k_sum(&dups, 2, 10),
+            vec![vec![0, 1], vec![0, 2], vec![1, 2]]
+        );
+        assert_eq!(
+            find_k_sum(&dups, 3, 10),
+            vec![vec![0, 3, 4], vec![1, 3, 4], vec![2, 3, 4]]
+        );
+        assert_eq!(
+            find_k_sum(&[i64::MAX, i64::MAX, -1], 3, i64::MAX - 1).len(),
+            0
+        );
+        assert_eq!(
+            find_k_sum(&[i64::MAX, i64::MAX, i64::MIN], 3, i64::MAX - 1).len(),
+            1
+        );
+    }
+    fn brute_force(values: &[i64], k: usize, target: i64) -> Vec<Vec<usize>> {
+        fn go(
+            values: &[i64],
+            k: usize,
+            target: i128,
+            start: usize,
+            chosen: &mut Vec<usize>,
+            out: &mut Vec<Vec<usize>>,
+        ) {
+            if k == 0 {
+                if target == 0 {
+                    out.push(chosen.clone());
+                }
+                return;
+            }
+            for ix in start..values.len() {
+                chosen.push(ix);
+                go(
+                    values,
+                    k - 1,
+                    target - i128::from(values[ix]),
+                    ix + 1,
+                    chosen,
+                    out,
+                );
+                chosen.pop();
+            }
+        }
+        let mut out = Vec::new();
+        go(values, k, i128::from(target), 0, &mut Vec::new(), &mut out);
+        out
+    }
+
+    fn values(ixs: &[usize], values: &[i64]) -> Vec<i64> {
+        ixs.iter().map(|ix| values[*ix]).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn pairs_match_brute_force(input in prop::collection::vec(-50i64..50, 0..30), target in -100i64..100) {
+            let expected = brute_force(&input, 2, target);
+            match find_expenses(&input, target) {
+                Some((a, b)) => {
+                    prop_assert_eq!(a + b, target);
+                    prop_assert!(expected.iter().any(|ixs| values(ixs, &input) == vec![a, b]));
+                }
+                None => prop_assert!(expected.is_empty()),
+            }
+        }
+
+        #[test]
+        fn triples_match_brute_force(input in prop::collection::vec(-50i64..50, 0..20), target in -150i64..150) {
+            let expected = brute_force(&input, 3, target);
+            match find_triple_expenses(&input, target) {
+                Some((a, b, c)) => {
+                    prop_assert!(expected.iter().any(|ixs| values(ixs, &input) == vec![a, b, c]));
+                }
+                None => prop_assert!(expected.is_empty()),
+            }
+        }
+
+        #[test]
+        fn k_sum_matches_brute_force(input in prop::collection::vec(-20i64..20, 0..12), k in 0usize..5, target in -40i64..40) {
+            let mut found = find_k_sum(&input, k, target);
+            found.sort();
+            prop_assert_eq!(found, brute_force(&input, k, target));
+        }
+    }
+
+    #[test]
+    fn k_sum_report() {
+        let ex = "1721\n979\n366\n299\n675\n1456\n";
+        assert_eq!(
+            report(ex, 3, 2020, false).unwrap(),
+            "979 + 366 + 675 = 2020, product 241861950"
+        );
+        assert_eq!(
+            report("5\n5\n-5\n10", 2, 0, true).unwrap(),
+            "5 + -5 = 0, product -25\n5 + -5 = 0, product -25"
+        );
+        assert!(report(ex, 4, 2020, true).is_err());
+    }
+    #[test]
+    fn whitespace_separated() {
+        assert_eq!(
+            parse("1721 979\n  366\t299\n\n675 1456\n").unwrap(),
+            vec![1721, 979, 366, 299, 675, 1456]
+        );
+        let e = parse("1721\n979 36x 299\n").unwrap_err();
+        let e = e.downcast_ref::<crate::parsing::ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (2, 5..8));
+    }
+    #[test]
     fn ex2_res() {
         let input = vec![1722, 979, 366, 299, 675, 1456];
         assert_eq!(pt2_impl(input).unwrap(), 241861950)
@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::parsing::{parse_at, parse_lines};
+use anyhow::Result;
 use std::{collections::HashMap, fmt};
 
 pub fn part1(input: &str) -> Result<impl fmt::Display> {
@@ -27,6 +28,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(combos(0, adapters.as_slice(), &mut cache))
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 fn combos(start: i64, adapters: &[i64], cache: &mut HashMap<i64, usize>) -> usize {
     if let Some(s) = cache.get(&start) {
         return *s;
@@ -52,13 +57,7 @@ fn combos(start: i64, adapters: &[i64], cache: &mut HashMap<i64, usize>) -> usiz
 }
 
 fn parse(input: &str) -> Result<Vec<i64>> {
-    input
-        .lines()
-        .map(|l| {
-            l.parse::<i64>()
-                .with_context(|| format!("could not parse number: {:?}", l))
-        })
-        .collect()
+    Ok(parse_lines(input, |l| parse_at(l, l, "a number"))?)
 }
 
 #[cfg(test)]
@@ -19,6 +19,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(b.count())
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 struct Board {
     a: FixedGrid<Tile>,
     b: FixedGrid<Tile>,
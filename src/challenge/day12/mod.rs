@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::parsing::{parse_at, parse_lines, ParseError};
+use anyhow::Result;
 use aoc::{grid::compass::Direction, Point};
 use std::fmt;
 
@@ -21,6 +22,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(manhattan_distance(ship.postion))
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 struct Instruction {
     command: Command,
@@ -140,24 +145,23 @@ impl Ship {
 }
 
 fn parse(input: &str) -> Result<Vec<Instruction>> {
-    input.lines().map(|l| parse_instr(l)).collect()
-}
-
-fn parse_instr(s: &str) -> Result<Instruction> {
-    let command = match &s[0..1] {
-        "N" => Command::North,
-        "S" => Command::South,
-        "E" => Command::East,
-        "W" => Command::West,
-        "F" => Command::Forward,
-        "R" => Command::Right,
-        "L" => Command::Left,
-        c => anyhow::bail!("unknown cmd: {:?}", c),
+    Ok(parse_lines(input, parse_instr)?)
+}
+
+fn parse_instr(s: &str) -> Result<Instruction, ParseError> {
+    let mut chars = s.chars();
+    let command = match chars.next() {
+        Some('N') => Command::North,
+        Some('S') => Command::South,
+        Some('E') => Command::East,
+        Some('W') => Command::West,
+        Some('F') => Command::Forward,
+        Some('R') => Command::Right,
+        Some('L') => Command::Left,
+        Some(c) => return Err(ParseError::new(s, 0..c.len_utf8(), "a command")),
+        None => return Err(ParseError::after(s, s, "a command")),
     };
-    let numeric = &s[1..];
-    let magnitude = numeric
-        .parse::<i64>()
-        .with_context(|| format!("could not parse number: {:?}", numeric))?;
+    let magnitude = parse_at(s, chars.as_str(), "a number")?;
     Ok(Instruction { command, magnitude })
 }
 
@@ -183,4 +187,12 @@ mod tests {
     fn p2_ex1() {
         assert_eq!(format!("{}", part2(EX).unwrap()), "286")
     }
+    #[test]
+    fn malformed_instr() {
+        let e = parse("F10\n\nN3\n").unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (2, 1..1));
+        assert_eq!(parse_instr("X3").unwrap_err().columns(), 1..2);
+        assert_eq!(parse_instr("R9o").unwrap_err().columns(), 2..4);
+    }
 }
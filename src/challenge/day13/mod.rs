@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::parsing::{parse_at, ParseError};
+use anyhow::Result;
 use std::fmt;
 
 pub fn part1(input: &str) -> Result<impl fmt::Display> {
@@ -19,6 +20,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(format!("{}", x))
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 fn next_bus(at: i64, candidates: &[&i64]) -> Result<(i64, i64)> {
     candidates
         .iter()
@@ -37,32 +42,28 @@ fn parse(input: &str) -> Result<(i64, Vec<Option<i64>>)> {
     let line1 = lines
         .next()
         .ok_or_else(|| anyhow::anyhow!("could not get first line"))?;
-    let depart_time = line1
-        .parse::<i64>()
-        .with_context(|| format!("could not parse number: {:?}", line1))?;
+    let depart_time = parse_at(line1, line1, "a departure time")?;
 
     let line2 = lines
         .next()
         .ok_or_else(|| anyhow::anyhow!("could not get second line"))?;
 
-    let bus_data = parse_schedule(line2)?;
+    let bus_data = parse_schedule(line2).map_err(|e| e.on_line(2))?;
 
     Ok((depart_time, bus_data))
 }
 
-fn parse_schedule(input: &str) -> Result<Vec<Option<i64>>> {
+fn parse_schedule(input: &str) -> Result<Vec<Option<i64>>, ParseError> {
     input
-        .split(",")
+        .split(',')
         .map(|s| {
             if s == "x" {
                 Ok(None)
             } else {
-                s.parse::<i64>()
-                    .map(|n| Some(n))
-                    .with_context(|| format!("could not parse number: {:?}", s))
+                parse_at(input, s, "a bus id or 'x'").map(Some)
             }
         })
-        .collect::<Result<Vec<_>>>()
+        .collect()
 }
 
 pub fn chain_offset(c: &[Option<i64>]) -> Result<i64> {
@@ -1,6 +1,7 @@
 use std::{fmt, u64};
 
-use anyhow::{Context, Result};
+use crate::parsing::{parse_at, parse_lines, split_once, ParseError};
+use anyhow::Result;
 
 const MASK_WIDTH: usize = 36;
 
@@ -22,33 +23,54 @@ impl Mask {
     }
 }
 
-pub fn parse_mask(input: &str) -> Result<Mask> {
+/// Parse a mask from `input`, a slice of `line`
+pub fn parse_mask(line: &str, input: &str) -> Result<Mask, ParseError> {
     let mut ones = 0u64;
     let mut zeros = 0u64;
 
-    for (idx, c) in input.chars().enumerate() {
+    check_width(line, input)?;
+    for (idx, (pos, c)) in input.char_indices().enumerate() {
         let set_bit = 1u64 << (MASK_WIDTH - idx - 1);
         match c {
             '0' => zeros |= set_bit,
             '1' => ones |= set_bit,
             'X' => continue,
-            _ => anyhow::bail!("mask can not include char: {:?}", c),
+            _ => {
+                let c = &input[pos..pos + c.len_utf8()];
+                return Err(ParseError::at(line, c, "'0', '1' or 'X'"));
+            }
         }
     }
     Ok(Mask { ones, zeros })
 }
 
-fn parse_floating(input: &str) -> Result<FloatingAddr> {
+fn check_width(line: &str, input: &str) -> Result<(), ParseError> {
+    match input.char_indices().nth(MASK_WIDTH) {
+        Some((pos, _)) => Err(ParseError::at(line, &input[pos..], "end of mask")),
+        None if input.chars().count() < MASK_WIDTH => Err(ParseError::after(
+            line,
+            input,
+            format!("{} mask bits", MASK_WIDTH),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn parse_floating(input: &str) -> Result<FloatingAddr, ParseError> {
     let mut start = 0u64;
     let mut float = 0u64;
 
-    for (idx, c) in input.chars().enumerate() {
+    check_width(input, input)?;
+    for (idx, (pos, c)) in input.char_indices().enumerate() {
         let set_bit = 1u64 << (MASK_WIDTH - idx - 1);
         match c {
             '0' => continue,
             '1' => start |= set_bit,
             'X' => float |= set_bit,
-            _ => anyhow::bail!("addr can not include char: {:?}", c),
+            _ => {
+                let c = &input[pos..pos + c.len_utf8()];
+                return Err(ParseError::at(input, c, "'0', '1' or 'X'"));
+            }
         }
     }
     Ok(FloatingAddr {
@@ -80,30 +102,21 @@ enum Instruction {
     SetValue(usize, u64),
 }
 
-fn parse_instruction(input: &str) -> Result<Instruction> {
+fn parse_instruction(input: &str) -> Result<Instruction, ParseError> {
     const MASK_LEADER: &str = "mask = ";
     const MEM_LEADER: &str = "mem[";
+    const MEM_SEP: &str = "] = ";
 
-    let instr = if input.starts_with(MASK_LEADER) {
-        let mask_str = &input[MASK_LEADER.len()..];
-        let mask = parse_mask(mask_str)?;
+    let instr = if let Some(mask_str) = input.strip_prefix(MASK_LEADER) {
+        let mask = parse_mask(input, mask_str)?;
         Instruction::SetMask(mask)
-    } else if input.starts_with(MEM_LEADER) {
-        let end_addr = input
-            .find("]")
-            .ok_or_else(|| anyhow::anyhow!("mem instruction was malformed: {:?}", input))?;
-        let addr_text = &input[MEM_LEADER.len()..end_addr];
-        let addr = addr_text
-            .parse::<usize>()
-            .with_context(|| format!("could not parse address: {:?}", input))?;
-        let value_text = &input[(end_addr + 4)..];
-        let value = value_text
-            .parse::<u64>()
-            .with_context(|| format!("could not parse value: {:?}", input))?;
-
+    } else if let Some(mem) = input.strip_prefix(MEM_LEADER) {
+        let (addr_text, value_text) = split_once(input, mem, MEM_SEP)?;
+        let addr = parse_at(input, addr_text, "an address")?;
+        let value = parse_at(input, value_text, "a value")?;
         Instruction::SetValue(addr, value)
     } else {
-        anyhow::bail!("unknown instruction: {:?}", input);
+        return Err(ParseError::at(input, input, "\"mask = \" or \"mem[\""));
     };
 
     Ok(instr)
@@ -113,10 +126,7 @@ pub struct Program(Vec<Instruction>);
 
 impl Program {
     pub fn parse(input: &str) -> Result<Program> {
-        let instrs = input
-            .lines()
-            .map(|l| parse_instruction(l))
-            .collect::<Result<Vec<_>>>()?;
+        let instrs = parse_lines(input, parse_instruction)?;
         Ok(Program(instrs))
     }
 }
@@ -315,47 +325,67 @@ impl MaskCpuV1 {
 mod tests {
     use super::*;
 
+    fn mask(s: &str) -> Mask {
+        parse_mask(s, s).unwrap()
+    }
+
     #[test]
     fn parse_empty_mask() {
-        let mask = parse_mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX").unwrap();
+        let mask = mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX");
         assert_eq!(mask.ones, 0);
         assert_eq!(mask.zeros, 0);
     }
 
     #[test]
     fn parse_zero_small() {
-        let mask = parse_mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0").unwrap();
+        let mask = mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0");
         assert_eq!(mask.ones, 0);
         assert_eq!(mask.zeros, 1);
     }
     #[test]
     fn parse_one_small() {
-        let mask = parse_mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX1").unwrap();
+        let mask = mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX1");
         assert_eq!(mask.ones, 1);
         assert_eq!(mask.zeros, 0);
     }
 
     #[test]
     fn parse_zero_large() {
-        let mask = parse_mask("0XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX").unwrap();
+        let mask = mask("0XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX");
         assert_eq!(mask.ones, 0);
         assert_eq!(mask.zeros, 34359738368);
     }
     #[test]
     fn parse_mixed() {
-        let mask = parse_mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0X101X").unwrap();
+        let mask = mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0X101X");
         assert_eq!(mask.ones, 10);
         assert_eq!(mask.zeros, 36);
     }
 
     #[test]
     fn example_text() {
-        let mask = parse_mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X").unwrap();
+        let mask = mask("XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X");
         assert_eq!(mask.apply(11), 73);
         assert_eq!(mask.apply(101), 101);
         assert_eq!(mask.apply(0), 64);
     }
 
+    #[test]
+    fn malformed_instructions() {
+        let e = Program::parse("mem[8] = 11\nmem[8]").err().unwrap();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (2, 7..7));
+        let e = parse_instruction("mem[8] = 11x").err().unwrap();
+        assert_eq!(e.columns(), 10..13);
+        let e = parse_instruction("mask = XX").err().unwrap();
+        assert_eq!(e.columns(), 10..10);
+        let e = parse_instruction("mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX0")
+            .err()
+            .unwrap();
+        assert_eq!(e.columns(), 44..45);
+        assert!(parse_instruction("nop").is_err());
+    }
+
     fn check_union(a: &str, b: &str, e: &str) {
         let a1 = parse_floating(a).unwrap();
         let a2 = parse_floating(b).unwrap();
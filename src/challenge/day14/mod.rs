@@ -13,6 +13,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(mask_cpu::execute_v4(&p)?)
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    mask_cpu::Program::parse(input).map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
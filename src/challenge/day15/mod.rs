@@ -1,3 +1,4 @@
+use crate::parsing::parse_at;
 use anyhow::Result;
 use std::fmt;
 mod memory_sequence;
@@ -15,21 +16,29 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(number_at(&starting, PT2_SPOKEN))
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse_input(input).map(|_| ())
+}
+
 fn number_at(starting: &[u32], target: usize) -> u32 {
     let mut game = memory_sequence::Sequence::init(starting, target);
     game.nth(target - starting.len() - 1).unwrap()
 }
 
 fn parse_input(s: &str) -> Result<Vec<u32>> {
-    s.split_whitespace()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.split(','))
-        .flatten()
-        .map(|s| {
-            s.parse::<u32>()
-                .map_err(|e| anyhow::format_err!("could not parse {:?}: {}", s, e))
-        })
-        .collect::<Result<Vec<_>>>()
+    let mut numbers = Vec::new();
+    for (idx, line) in s.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        for n in trimmed.split(',') {
+            let n =
+                parse_at(line, n.trim(), "a starting number").map_err(|e| e.on_line(idx + 1))?;
+            numbers.push(n);
+        }
+    }
+    Ok(numbers)
 }
 
 #[cfg(test)]
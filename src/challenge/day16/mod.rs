@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::parsing::{parse_at, split_once, ParseError};
+use anyhow::Result;
 use std::fmt;
 
 pub fn part1(input: &str) -> Result<impl fmt::Display> {
@@ -31,54 +32,61 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(format!("{:?}", 0))
 }
 
-fn extract_split<'a>(segments: &mut impl Iterator<Item = &'a str>) -> Result<&'a str> {
-    segments
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("segment missing"))
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
 }
 
 fn parse(input: &str) -> Result<(Vec<Class>, Ticket, Vec<Ticket>)> {
     let mut segments = input.split("\n\n");
-    let mut get_chunk = || {
-        segments
+    let mut first_line = 1;
+    let mut get_chunk = |name: &str| {
+        let chunk = segments
             .next()
-            .ok_or_else(|| anyhow::anyhow!("unable to parse: {:?}", input))
+            .ok_or_else(|| anyhow::anyhow!("missing section: {}", name))?;
+        let line = first_line;
+        first_line += chunk.lines().count() + 1;
+        Ok::<_, anyhow::Error>((line, chunk))
     };
-    let classes_str = get_chunk()?;
-    let my_ticket_str = get_chunk()?;
-    let other_tikets_str = get_chunk()?;
+    let (classes_line, classes_str) = get_chunk("classes")?;
+    let (my_ticket_line, my_ticket_str) = get_chunk("your ticket")?;
+    let (other_tickets_line, other_tickets_str) = get_chunk("nearby tickets")?;
 
-    let classes = classes_str
-        .lines()
-        .map(parse_class)
-        .collect::<Result<Vec<_>>>()?;
+    let classes = parse_section(classes_line, classes_str, 0, parse_class)?;
 
-    let ticket = my_ticket_str
-        .lines()
-        .skip(1)
-        .map(parse_ticket)
+    let ticket = parse_section(my_ticket_line, my_ticket_str, 1, parse_ticket)?
+        .into_iter()
         .next()
-        .ok_or_else(|| anyhow::anyhow!("my ticket did not exist"))??;
+        .ok_or_else(|| anyhow::anyhow!("my ticket did not exist"))?;
 
-    let other_tickets = other_tikets_str
-        .lines()
-        .skip(1)
-        .map(parse_ticket)
-        .collect::<Result<Vec<Ticket>>>()?;
+    let other_tickets = parse_section(other_tickets_line, other_tickets_str, 1, parse_ticket)?;
 
     Ok((classes, ticket, other_tickets))
 }
 
-fn parse_class(input: &str) -> Result<Class> {
-    let mut segments = input.split(':');
-    let class_name = extract_split(&mut segments).context("class name")?;
-    let ranges = extract_split(&mut segments).context("class name")?.trim();
-    let mut segments = ranges.split(" or ");
-    let r1_str = extract_split(&mut segments).context("range #1")?;
-    let r2_str = extract_split(&mut segments).context("range #2")?;
+/// Parse the lines of a section which starts at `first_line`, after skipping its header
+fn parse_section<'a, T, F>(
+    first_line: usize,
+    section: &'a str,
+    header: usize,
+    f: F,
+) -> Result<Vec<T>, ParseError>
+where
+    F: Fn(&'a str) -> Result<T, ParseError>,
+{
+    section
+        .lines()
+        .enumerate()
+        .skip(header)
+        .map(|(idx, l)| f(l).map_err(|e| e.on_line(first_line + idx)))
+        .collect()
+}
+
+fn parse_class(input: &str) -> Result<Class, ParseError> {
+    let (class_name, ranges) = split_once(input, input, ":")?;
+    let (r1_str, r2_str) = split_once(input, ranges.trim(), " or ")?;
 
-    let r1 = parse_range(r1_str)?;
-    let r2 = parse_range(r2_str)?;
+    let r1 = parse_range(input, r1_str)?;
+    let r2 = parse_range(input, r2_str)?;
 
     log::trace!("class {:?} r1: {:?} r2: {:?}", class_name, r1, r2);
 
@@ -89,29 +97,20 @@ fn parse_class(input: &str) -> Result<Class> {
     })
 }
 
-fn parse_range(input: &str) -> Result<Range> {
-    let mut segments = input.split('-');
-    let start_str = extract_split(&mut segments).context("range_start")?;
-    let end_str = extract_split(&mut segments).context("range_end")?.trim();
+fn parse_range(line: &str, input: &str) -> Result<Range, ParseError> {
+    let (start_str, end_str) = split_once(line, input, "-")?;
     Ok(Range {
-        start: start_str
-            .parse::<i64>()
-            .with_context(|| format!("could not parse number: {:?}", start_str))?,
-        end: end_str
-            .parse::<i64>()
-            .with_context(|| format!("could not parse number: {:?}", end_str))?,
+        start: parse_at(line, start_str, "a range start")?,
+        end: parse_at(line, end_str.trim(), "a range end")?,
     })
 }
 
-fn parse_ticket(input: &str) -> Result<Ticket> {
+fn parse_ticket(input: &str) -> Result<Ticket, ParseError> {
     Ok(Ticket(
         input
             .split(',')
-            .map(|s| {
-                s.parse::<i64>()
-                    .with_context(|| format!("unable to parse number from: {:?}", input))
-            })
-            .collect::<Result<Vec<i64>>>()?,
+            .map(|s| parse_at(input, s, "a ticket value"))
+            .collect::<Result<Vec<i64>, _>>()?,
     ))
 }
 
@@ -176,4 +175,16 @@ mod tests {
         assert!(!r.contains(&2));
         assert!(!r.contains(&6));
     }
+
+    #[test]
+    fn malformed_sections() {
+        let input = EX.replace("40,4,50", "40,four,50");
+        let e = parse(&input).unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (10, 4..8));
+        let input = EX.replace("row: 6-11 or", "row: 6-11 and");
+        let e = parse(&input).unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.expected()), (2, "\" or \""));
+    }
 }
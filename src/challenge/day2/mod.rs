@@ -1,98 +1,158 @@
-use anyhow::{anyhow as ah, Context, Result};
-use std::{convert::TryFrom, fmt, str::FromStr};
+use crate::parsing::{parse_at, parse_lines, split_once, ParseError};
+use anyhow::{Context, Result};
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{BufRead, Read},
+};
+
+pub use self::policy::parse_policy;
+use self::policy::{CountRange, OnePosition, Policy};
+
+mod policy;
 
 pub fn part1(input: &str) -> Result<impl fmt::Display> {
     let pws = parse(input)?;
-    Ok(pws.iter().filter(|pe| pe.check()).count())
+    Ok(pws.iter().filter(|pe| CountRange.check(pe)).count())
 }
 pub fn part2(input: &str) -> Result<impl fmt::Display> {
     let pws = parse(input)?;
-    Ok(pws.iter().filter(|pe| pe.check_v2()).count())
+    Ok(pws.iter().filter(|pe| OnePosition.check(pe)).count())
 }
 
-fn parse(input: &str) -> Result<Vec<PasswordEntry<'_>>> {
-    input
-        .lines()
-        .map(|s| {
-            PasswordEntry::try_from(s).with_context(|| format!("parse password entry: '{}'", s))
-        })
-        .collect()
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
 }
 
-#[derive(Debug)]
-struct PasswordPolicy {
-    min: u32,
-    max: u32,
-    c: char,
+fn parse(input: &str) -> Result<Vec<PasswordEntry<'_>>> {
+    Ok(parse_lines(input, PasswordEntry::try_from)?)
 }
 
-impl PasswordPolicy {
-    fn check(&self, s: &str) -> bool {
-        let count = s.chars().filter(|c| *c == self.c).count() as u32;
-        count >= self.min && count <= self.max
-    }
-    fn check_v2(&self, s: &str) -> bool {
-        let count = s
-            .chars()
-            .enumerate()
-            .filter(|(idx, c)| {
-                let pos = (*idx + 1) as u32;
-                (pos == self.min || pos == self.max) && *c == self.c
-            })
-            .count() as u32;
-        count == 1
+/// List every entry which breaks any of `policies` and why, then how many entries follow
+/// all of them
+pub fn report(input: &str, policies: &[Box<dyn Policy>]) -> Result<String> {
+    let pws = parse(input)?;
+    let mut out = Vec::new();
+    let mut valid = 0;
+    for (idx, entry) in pws.iter().enumerate() {
+        let violations = policies
+            .iter()
+            .filter_map(|p| p.violation(entry).map(|why| format!("{}: {}", p, why)))
+            .collect::<Vec<_>>();
+        if violations.is_empty() {
+            valid += 1;
+        }
+        for why in violations {
+            out.push(format!("line {}: {}: {}", idx + 1, entry, why));
+        }
     }
+    let names = policies
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    out.push(format!(
+        "{} of {} entries follow {}",
+        valid,
+        pws.len(),
+        names
+    ));
+    Ok(out.join("\n"))
 }
 
-impl FromStr for PasswordPolicy {
-    type Err = anyhow::Error;
+/// How many entries of a database follow each of the puzzle's policies
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    pub entries: usize,
+    pub count: usize,
+    pub position: usize,
+}
 
-    fn from_str(s: &str) -> Result<PasswordPolicy, Self::Err> {
-        let mut split = s.splitn(2, ' ').into_iter();
-        let minmax_str = split.next().ok_or_else(|| ah!("no text in policy"))?;
-        let char = split
-            .next()
-            .ok_or_else(|| ah!("no space in '<min>-<max> <char>'"))?;
-        let c = char.chars().nth(0).ok_or_else(|| ah!("chars was empty"))?;
+/// The longest line `count_stream` reads, in bytes and not counting the line ending
+pub const MAX_LINE: usize = 64 * 1024;
 
-        let mut minmax_split = minmax_str.splitn(2, '-').into_iter();
-        let min_str = minmax_split
-            .next()
-            .ok_or_else(|| ah!("minmax string empty"))?;
-        let max_str = minmax_split
-            .next()
-            .ok_or_else(|| ah!("minmax string had no hyphen"))?;
+/// Count entries one line at a time, reusing a single line buffer, so a database of any size
+/// is checked in constant memory. A line longer than `MAX_LINE` is an error.
+pub fn count_stream<R: BufRead>(mut reader: R) -> Result<Counts> {
+    let mut counts = Counts::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = (&mut reader)
+            .take(MAX_LINE as u64 + 2)
+            .read_until(b'\n', &mut line)
+            .with_context(|| format!("Unable to read line {}", counts.entries + 1))?;
+        if read == 0 {
+            return Ok(counts);
+        }
+        counts.entries += 1;
+        // same line endings as `str::lines`
+        let text = match line.strip_suffix(b"\n") {
+            Some(l) => l.strip_suffix(b"\r").unwrap_or(l),
+            None => &line,
+        };
+        if text.len() > MAX_LINE {
+            anyhow::bail!("line {} is longer than {} bytes", counts.entries, MAX_LINE);
+        }
+        let text = std::str::from_utf8(text)
+            .with_context(|| format!("line {} is not valid UTF-8", counts.entries))?;
+        let entry = PasswordEntry::try_from(text).map_err(|e| e.on_line(counts.entries))?;
+        if CountRange.check(&entry) {
+            counts.count += 1;
+        }
+        if OnePosition.check(&entry) {
+            counts.position += 1;
+        }
+    }
+}
 
-        let min = min_str.parse::<u32>()?;
-        let max = max_str.parse::<u32>()?;
+#[derive(Debug)]
+struct PasswordPolicy {
+    min: u32,
+    max: u32,
+    c: char,
+}
 
+impl PasswordPolicy {
+    /// Parse `<min>-<max> <char>` from `s`, a slice of `line`
+    fn parse(line: &str, s: &str) -> Result<PasswordPolicy, ParseError> {
+        let (minmax, c) = split_once(line, s, " ")?;
+        let (min, max) = split_once(line, minmax, "-")?;
+        let min = parse_at(line, min, "a minimum count")?;
+        let max = parse_at(line, max, "a maximum count")?;
+        let mut chars = c.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            (None, _) => return Err(ParseError::after(line, c, "a character")),
+            (Some(_), Some(_)) => return Err(ParseError::at(line, c, "a single character")),
+        };
         Ok(PasswordPolicy { min, max, c })
     }
 }
 
 #[derive(Debug)]
-struct PasswordEntry<'a> {
+pub struct PasswordEntry<'a> {
     policy: PasswordPolicy,
     passwd: &'a str,
 }
 
-impl<'a> PasswordEntry<'a> {
-    fn check(&self) -> bool {
-        self.policy.check(self.passwd)
-    }
-    fn check_v2(&self) -> bool {
-        self.policy.check_v2(self.passwd)
+impl<'a> fmt::Display for PasswordEntry<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} {}: {}",
+            self.policy.min, self.policy.max, self.policy.c, self.passwd
+        )
     }
 }
 
 impl<'a> TryFrom<&'a str> for PasswordEntry<'a> {
-    type Error = anyhow::Error;
+    type Error = ParseError;
 
     fn try_from(s: &'a str) -> Result<PasswordEntry<'a>, Self::Error> {
-        let mut split = s.splitn(2, ':').into_iter();
-        let policy_str = split.next().ok_or_else(|| ah!("no text in entry"))?;
-        let password = split.next().ok_or_else(|| ah!("no ':' in entry"))?.trim();
-        let policy = PasswordPolicy::from_str(policy_str).context("could not parse policy")?;
+        let (policy_str, password) = split_once(s, s, ":")?;
+        let policy = PasswordPolicy::parse(s, policy_str)?;
+        let password = password.trim();
         let entry = PasswordEntry {
             policy,
             passwd: password,
@@ -115,4 +175,65 @@ mod tests {
     fn verify_p2() {
         assert_eq!(format!("{}", part2(INPUT).unwrap()), "451")
     }
+    #[test]
+    fn policy_report() {
+        let ex = "1-3 a: abcde\n1-3 b: cdefg\n2-9 c: ccccccccc\n";
+        let policies = vec![
+            parse_policy("position").unwrap(),
+            parse_policy("distinct:2").unwrap(),
+        ];
+        assert_eq!(
+            report(ex, &policies).unwrap(),
+            "line 2: 1-3 b: cdefg: position: 'b' is at neither position 1 nor 3\n\
+             line 3: 2-9 c: ccccccccc: position: 'c' is at both positions 2 and 9\n\
+             line 3: 2-9 c: ccccccccc: distinct 2: has 1 distinct characters, expected at least 2\n\
+             1 of 3 entries follow position, distinct 2"
+        );
+    }
+    #[test]
+    fn streamed_counts() {
+        let counts = count_stream(INPUT.as_bytes()).unwrap();
+        assert_eq!(
+            counts,
+            Counts {
+                entries: 1000,
+                count: 422,
+                position: 451,
+            }
+        );
+        let ex = "1-3 a: abcde\r\n1-3 b: cdefg\r\n2-9 c: ccccccccc";
+        assert_eq!(
+            count_stream(ex.as_bytes()).unwrap(),
+            Counts {
+                entries: 3,
+                count: 2,
+                position: 1,
+            }
+        );
+        assert_eq!(count_stream(&b""[..]).unwrap(), Counts::default());
+        let e = count_stream(&b"1-3 a: abcde\n1-x b: cdefg\n"[..]).unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (2, 3..4));
+    }
+    #[test]
+    fn streamed_line_limit() {
+        let longest = format!("1-1 a: a{}\r\n", "b".repeat(MAX_LINE - 8));
+        assert_eq!(count_stream(longest.as_bytes()).unwrap().count, 1);
+        let too_long = format!("1-3 a: abcde\n1-1 a: {}\n", "a".repeat(MAX_LINE - 6));
+        let e = count_stream(too_long.as_bytes()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!("line 2 is longer than {} bytes", MAX_LINE)
+        );
+        assert!(count_stream(&b"1-3 a: ab\xffde\n"[..]).is_err());
+    }
+    #[test]
+    fn malformed_entry() {
+        let e = parse("1-3 a: abcde\n1-x b: cdefg\n").unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!(e.line(), 2);
+        assert_eq!(e.columns(), 3..4);
+        assert!(parse("1-3 a abcde").is_err());
+        assert!(parse("1-3 : abcde").is_err());
+    }
 }
@@ -0,0 +1,296 @@
+use super::PasswordEntry;
+use anyhow::{anyhow as ah, Context, Result};
+use std::{collections::HashSet, fmt};
+
+/// A rule a password entry has to follow
+pub trait Policy: fmt::Display {
+    /// Why the entry breaks this rule, or `None` if it follows it
+    fn violation(&self, entry: &PasswordEntry<'_>) -> Option<String>;
+
+    fn check(&self, entry: &PasswordEntry<'_>) -> bool {
+        self.violation(entry).is_none()
+    }
+}
+
+/// The entry's character appears between its min and max times
+#[derive(Debug, Clone, Copy)]
+pub struct CountRange;
+
+impl Policy for CountRange {
+    fn violation(&self, entry: &PasswordEntry<'_>) -> Option<String> {
+        let rule = &entry.policy;
+        let count = entry.passwd.chars().filter(|c| *c == rule.c).count() as u32;
+        if count >= rule.min && count <= rule.max {
+            None
+        } else {
+            Some(format!(
+                "{:?} appears {} times, expected {} to {}",
+                rule.c, count, rule.min, rule.max
+            ))
+        }
+    }
+}
+
+impl fmt::Display for CountRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "count")
+    }
+}
+
+/// The entry's character is at exactly one of its two positions, counting from 1
+#[derive(Debug, Clone, Copy)]
+pub struct OnePosition;
+
+impl Policy for OnePosition {
+    fn violation(&self, entry: &PasswordEntry<'_>) -> Option<String> {
+        let rule = &entry.policy;
+        let at = |pos: u32| {
+            pos > 0
+                && entry
+                    .passwd
+                    .chars()
+                    .nth(pos as usize - 1)
+                    .map(|c| c == rule.c)
+                    .unwrap_or(false)
+        };
+        match (at(rule.min), at(rule.max)) {
+            (true, false) | (false, true) => None,
+            (true, true) => Some(format!(
+                "{:?} is at both positions {} and {}",
+                rule.c, rule.min, rule.max
+            )),
+            (false, false) => Some(format!(
+                "{:?} is at neither position {} nor {}",
+                rule.c, rule.min, rule.max
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OnePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "position")
+    }
+}
+
+/// Every character of the password is in a class such as `[a-z0-9]`, or `[^xyz]` to allow
+/// everything else
+#[derive(Debug, Clone)]
+pub struct CharClass {
+    spec: String,
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    pub fn parse(spec: &str) -> Result<CharClass> {
+        let inner = spec
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| {
+                ah!(
+                    "expected a class in brackets, such as [a-z], got {:?}",
+                    spec
+                )
+            })?;
+        let (negated, inner) = match inner.strip_prefix('^') {
+            Some(rest) => (true, rest),
+            None => (false, inner),
+        };
+        let chars = inner.chars().collect::<Vec<_>>();
+        if chars.is_empty() {
+            anyhow::bail!("empty character class {:?}", spec);
+        }
+        let mut ranges = Vec::new();
+        let mut idx = 0;
+        while idx < chars.len() {
+            // a '-' at either end is a literal
+            if idx + 2 < chars.len() && chars[idx + 1] == '-' {
+                let (lo, hi) = (chars[idx], chars[idx + 2]);
+                if lo > hi {
+                    anyhow::bail!("range {}-{} in {:?} is backwards", lo, hi, spec);
+                }
+                ranges.push((lo, hi));
+                idx += 3;
+            } else {
+                ranges.push((chars[idx], chars[idx]));
+                idx += 1;
+            }
+        }
+        Ok(CharClass {
+            spec: spec.to_string(),
+            negated,
+            ranges,
+        })
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi) != self.negated
+    }
+}
+
+impl Policy for CharClass {
+    fn violation(&self, entry: &PasswordEntry<'_>) -> Option<String> {
+        entry
+            .passwd
+            .chars()
+            .enumerate()
+            .find(|(_, c)| !self.contains(*c))
+            .map(|(idx, c)| format!("{:?} at position {} is not in {}", c, idx + 1, self.spec))
+    }
+}
+
+impl fmt::Display for CharClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "class {}", self.spec)
+    }
+}
+
+/// The password does not contain any of these strings
+#[derive(Debug, Clone)]
+pub struct Forbidden(pub Vec<String>);
+
+impl Policy for Forbidden {
+    fn violation(&self, entry: &PasswordEntry<'_>) -> Option<String> {
+        self.0.iter().find_map(|s| {
+            entry.passwd.find(s.as_str()).map(|byte| {
+                let pos = entry.passwd[..byte].chars().count() + 1;
+                format!("contains {:?} at position {}", s, pos)
+            })
+        })
+    }
+}
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "forbid {}", self.0.join(","))
+    }
+}
+
+/// The password has at least this many different characters
+#[derive(Debug, Clone, Copy)]
+pub struct MinDistinct(pub usize);
+
+impl Policy for MinDistinct {
+    fn violation(&self, entry: &PasswordEntry<'_>) -> Option<String> {
+        let distinct = entry.passwd.chars().collect::<HashSet<_>>().len();
+        if distinct >= self.0 {
+            None
+        } else {
+            Some(format!(
+                "has {} distinct characters, expected at least {}",
+                distinct, self.0
+            ))
+        }
+    }
+}
+
+impl fmt::Display for MinDistinct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "distinct {}", self.0)
+    }
+}
+
+/// Parse a policy named on the command line, one of `count`, `position`, `class:[a-z]`,
+/// `forbid:abc,xyz` or `distinct:N`
+pub fn parse_policy(spec: &str) -> Result<Box<dyn Policy>> {
+    let (name, arg) = match spec.find(':') {
+        Some(idx) => (&spec[..idx], Some(&spec[idx + 1..])),
+        None => (spec, None),
+    };
+    Ok(match (name, arg) {
+        ("count", None) => Box::new(CountRange),
+        ("position", None) => Box::new(OnePosition),
+        ("class", Some(class)) => Box::new(CharClass::parse(class)?),
+        ("forbid", Some(list)) => {
+            let forbidden = list
+                .split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            if forbidden.is_empty() {
+                anyhow::bail!("forbid needs at least one string, such as forbid:abc");
+            }
+            Box::new(Forbidden(forbidden))
+        }
+        ("distinct", Some(n)) => Box::new(MinDistinct(
+            n.parse()
+                .with_context(|| format!("distinct count {:?}", n))?,
+        )),
+        _ => anyhow::bail!(
+            "unknown policy {:?}, expected one of count, position, class:[..], forbid:.., distinct:N",
+            spec
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn violation(policy: &str, entry: &str) -> Option<String> {
+        let entry = PasswordEntry::try_from(entry).unwrap();
+        parse_policy(policy).unwrap().violation(&entry)
+    }
+
+    #[test]
+    fn puzzle_policies() {
+        assert_eq!(violation("count", "1-3 a: abcde"), None);
+        assert_eq!(
+            violation("count", "1-3 b: cdefg").unwrap(),
+            "'b' appears 0 times, expected 1 to 3"
+        );
+        assert_eq!(violation("position", "1-3 a: abcde"), None);
+        assert_eq!(
+            violation("position", "1-3 b: cdefg").unwrap(),
+            "'b' is at neither position 1 nor 3"
+        );
+        assert_eq!(
+            violation("position", "2-9 c: ccccccccc").unwrap(),
+            "'c' is at both positions 2 and 9"
+        );
+        assert_eq!(violation("position", "0-2 c: ccc"), None);
+    }
+
+    #[test]
+    fn extra_policies() {
+        assert_eq!(violation("class:[a-e]", "1-3 a: abcde"), None);
+        assert_eq!(
+            violation("class:[a-cx-]", "1-3 a: ab-xd").unwrap(),
+            "'d' at position 5 is not in [a-cx-]"
+        );
+        assert_eq!(
+            violation("class:[^0-9]", "1-3 a: abc4").unwrap(),
+            "'4' at position 4 is not in [^0-9]"
+        );
+        assert_eq!(
+            violation("forbid:zz,cd", "1-3 a: abcde").unwrap(),
+            "contains \"cd\" at position 3"
+        );
+        assert_eq!(violation("distinct:5", "1-3 a: abcde"), None);
+        assert_eq!(
+            violation("distinct:3", "2-9 c: ccccccccc").unwrap(),
+            "has 1 distinct characters, expected at least 3"
+        );
+    }
+
+    #[test]
+    fn bad_specs() {
+        for spec in &[
+            "counts",
+            "count:1",
+            "class:a-z",
+            "class:[]",
+            "class:[z-a]",
+            "forbid:",
+            "distinct:x",
+        ] {
+            assert!(parse_policy(spec).is_err(), "{}", spec);
+        }
+        assert_eq!(
+            parse_policy("class:[a-z]").unwrap().to_string(),
+            "class [a-z]"
+        );
+    }
+}
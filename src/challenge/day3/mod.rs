@@ -1,13 +1,17 @@
 use anyhow::Result;
 use aoc::{
-    grid::{fixed_grid::FixedGrid, grid_types::GridHeight, repeat_grid::HorizontalRepeat},
+    grid::{fixed_grid::FixedGrid, grid_types::GridWidth, repeat_grid::HorizontalRepeat},
     Point,
 };
 use std::fmt;
 
+pub use self::path::{best_slope, path, render, trees_on, Travel};
+
+mod path;
+
 pub fn part1(input: &str) -> Result<impl fmt::Display> {
     let trees = parse(input)?;
-    Ok(trees_hit(&trees, Point::new(0, 0), Point::new(3, 1)))
+    trees_hit(&trees, Point::new(0, 0), Point::new(3, 1))
 }
 pub fn part2(input: &str) -> Result<impl fmt::Display> {
     let trees = parse(input)?;
@@ -18,27 +22,62 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
         Point::new(7, 1),
         Point::new(1, 2),
     ];
-    Ok(slopes
+    slopes
         .iter()
         .map(|s| trees_hit(&trees, Point::new(0, 0), *s))
-        .fold(1, |acc, i| acc * i))
+        .try_fold(1, |acc, i| i.map(|i| acc * i))
+}
+
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
 }
 
-struct Trees {
+pub struct Trees {
     inner: HorizontalRepeat<FixedGrid<bool>>,
+    /// Width of the pattern before it repeats
+    width: i64,
 }
 
-fn trees_hit(trees: &Trees, start: Point, slope: Point) -> usize {
-    (0..)
-        .map(|idx| start + Point::new(slope.x * idx, slope.y * idx))
-        .take_while(|p| p.y < trees.inner.height())
-        .filter(|p| trees.inner[*p])
-        .count()
+impl Trees {
+    fn at(&self, p: Point) -> bool {
+        self.inner[Point::new(p.x.rem_euclid(self.width), p.y)]
+    }
 }
 
-fn parse(input: &str) -> Result<Trees> {
+fn trees_hit(trees: &Trees, start: Point, slope: Point) -> Result<usize> {
+    Ok(trees_on(trees, &path(trees, start, slope, Travel::Steps)?))
+}
+
+/// Trace a slope from the top left, rendering the map with its path and how many trees it
+/// hits, or with `search` find the best slope up to `slope` instead
+pub fn report(input: &str, slope: Point, travel: Travel, search: bool) -> Result<String> {
+    let trees = parse(input)?;
+    let start = Point::new(0, 0);
+    let slope = if search {
+        match best_slope(&trees, start, slope, travel)? {
+            Some((best, _)) => best,
+            None => anyhow::bail!("no slopes to search, down has to be at least 1"),
+        }
+    } else {
+        slope
+    };
+    let path = path(&trees, start, slope, travel)?;
+    Ok(format!(
+        "{}{} right {} down hits {} trees",
+        render(&trees, &path),
+        slope.x,
+        slope.y,
+        trees_on(&trees, &path)
+    ))
+}
+
+pub fn parse(input: &str) -> Result<Trees> {
     let inner = FixedGrid::parse_ascii_grid(input, |c| Ok(c == '#'))?;
+    if inner.width() == 0 {
+        anyhow::bail!("the map is empty");
+    }
     Ok(Trees {
+        width: inner.width(),
         inner: HorizontalRepeat::new(inner),
     })
 }
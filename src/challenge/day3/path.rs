@@ -0,0 +1,236 @@
+use super::Trees;
+use anyhow::Result;
+use aoc::{grid::grid_types::GridHeight, Point};
+use std::collections::HashSet;
+
+/// How the toboggan moves down the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Travel {
+    /// Move by the whole slope each time, like the puzzle, so a slope going down more than
+    /// one row skips the rows in between
+    Steps,
+    /// Land on every row, at the column nearest the straight line of the slope, so slopes
+    /// such as 3 right 2 down don't skip rows
+    Line,
+}
+
+/// The widest map `render` draws, in columns
+const MAX_COLUMNS: i64 = 4096;
+
+/// Every point the toboggan lands on from `start`, until it goes past the bottom of the map.
+///
+/// `start` has to be on or below the top row, and the path can't go further right or left
+/// than an `i64` holds.
+pub fn path(trees: &Trees, start: Point, slope: Point, travel: Travel) -> Result<Vec<Point>> {
+    if slope.y <= 0 {
+        anyhow::bail!(
+            "slope has to go down, got {} right {} down",
+            slope.x,
+            slope.y
+        );
+    }
+    if start.y < 0 {
+        anyhow::bail!("start has to be on the map, got row {}", start.y);
+    }
+    let rows = (trees.inner.height() - start.y).max(0);
+    let steps = if rows == 0 {
+        0
+    } else {
+        (rows - 1) / slope.y + 1
+    };
+    let at = |dx: Option<i64>, dy: i64| Some(Point::new(start.x.checked_add(dx?)?, start.y + dy));
+    let points = match travel {
+        Travel::Steps => (0..steps)
+            .map(|idx| at(slope.x.checked_mul(idx), slope.y * idx))
+            .collect::<Option<Vec<_>>>(),
+        // x rounded half up, as the row's distance times right/down
+        Travel::Line => (0..rows)
+            .map(|dy| {
+                let dx = (2 * dy)
+                    .checked_mul(slope.x)
+                    .and_then(|x| x.checked_add(slope.y))
+                    .zip(slope.y.checked_mul(2))
+                    .map(|(x, down)| x.div_euclid(down));
+                at(dx, dy)
+            })
+            .collect(),
+    };
+    points.ok_or_else(|| {
+        anyhow::anyhow!(
+            "slope {} right {} down goes further than a column number holds",
+            slope.x,
+            slope.y
+        )
+    })
+}
+
+pub fn trees_on(trees: &Trees, path: &[Point]) -> usize {
+    path.iter().filter(|p| trees.at(**p)).count()
+}
+
+/// The map with each point of `path` marked as `O` on open ground or `X` on a tree, like the
+/// puzzle text. The pattern is repeated as many times as it takes to show the whole path.
+///
+/// At most `MAX_COLUMNS` columns are drawn, starting from the leftmost repeat, and a row whose
+/// point is cut off ends with `…` instead.
+pub fn render(trees: &Trees, path: &[Point]) -> String {
+    let on_path = path.iter().map(|p| (p.x, p.y)).collect::<HashSet<_>>();
+    let xs = path.iter().map(|p| p.x);
+    let first = xs.clone().min().unwrap_or(0).min(0).div_euclid(trees.width);
+    let last = xs.max().unwrap_or(0).max(0).div_euclid(trees.width);
+    let repeats = last
+        .saturating_sub(first)
+        .saturating_add(1)
+        .min((MAX_COLUMNS / trees.width).max(1));
+    let columns = first.saturating_mul(trees.width)..(first + repeats).saturating_mul(trees.width);
+    let cut_off = path
+        .iter()
+        .filter(|p| !columns.contains(&p.x))
+        .map(|p| p.y)
+        .collect::<HashSet<_>>();
+    (0..trees.inner.height())
+        .map(|y| {
+            let marker = if cut_off.contains(&y) { "…\n" } else { "\n" };
+            columns
+                .clone()
+                .map(|x| {
+                    let p = Point::new(x, y);
+                    match (on_path.contains(&(x, y)), trees.at(p)) {
+                        (true, true) => 'X',
+                        (true, false) => 'O',
+                        (false, true) => '#',
+                        (false, false) => '.',
+                    }
+                })
+                .collect::<String>()
+                + marker
+        })
+        .collect()
+}
+
+/// The slope hitting the fewest trees from `start`, out of every slope going `0..=max.x` right
+/// and `1..=max.y` down, along with how many it hits. Ties go to the slope going down the
+/// least, then right the least.
+pub fn best_slope(
+    trees: &Trees,
+    start: Point,
+    max: Point,
+    travel: Travel,
+) -> Result<Option<(Point, usize)>> {
+    let mut best: Option<(Point, usize)> = None;
+    for down in 1..=max.y {
+        for right in 0..=max.x {
+            let slope = Point::new(right, down);
+            let hit = trees_on(trees, &path(trees, start, slope, travel)?);
+            if best.map(|(_, fewest)| hit < fewest).unwrap_or(true) {
+                best = Some((slope, hit));
+            }
+        }
+    }
+    Ok(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse;
+    use super::*;
+
+    const EX: &str = include_str!("../../../input/day3_ex");
+
+    fn points(v: &[(i64, i64)]) -> Vec<Point> {
+        v.iter().map(|(x, y)| Point::new(*x, *y)).collect()
+    }
+
+    #[test]
+    fn steps_and_lines() {
+        let trees = parse(EX).unwrap();
+        let origin = Point::new(0, 0);
+        let steps = path(&trees, origin, Point::new(1, 2), Travel::Steps).unwrap();
+        assert_eq!(
+            steps,
+            points(&[(0, 0), (1, 2), (2, 4), (3, 6), (4, 8), (5, 10)])
+        );
+        assert_eq!(trees_on(&trees, &steps), 2);
+
+        let line = path(&trees, origin, Point::new(3, 2), Travel::Line).unwrap();
+        assert_eq!(line.len(), 11);
+        assert_eq!(&line[..4], &points(&[(0, 0), (2, 1), (3, 2), (5, 3)])[..]);
+        assert_eq!(line[10], Point::new(15, 10));
+
+        // an integer slope is the same either way
+        assert_eq!(
+            path(&trees, origin, Point::new(3, 1), Travel::Line).unwrap(),
+            path(&trees, origin, Point::new(3, 1), Travel::Steps).unwrap()
+        );
+        let left = path(&trees, Point::new(0, 9), Point::new(-3, 2), Travel::Line).unwrap();
+        assert_eq!(left, points(&[(0, 9), (-1, 10)]));
+        assert!(path(&trees, origin, Point::new(1, 0), Travel::Steps).is_err());
+        assert!(path(&trees, Point::new(0, -1), Point::new(1, 1), Travel::Steps).is_err());
+        assert!(path(&trees, Point::new(0, -1), Point::new(1, 1), Travel::Line).is_err());
+        assert!(
+            path(&trees, Point::new(0, 20), Point::new(1, 1), Travel::Steps)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn render_example() {
+        let trees = parse(EX).unwrap();
+        let steps = path(&trees, Point::new(0, 0), Point::new(3, 1), Travel::Steps).unwrap();
+        let map = render(&trees, &steps);
+        let lines = map.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "O.##.........##.........##.......");
+        assert_eq!(lines[1], "#..O#...#..#...#...#..#...#...#..");
+        assert_eq!(lines[10], ".#..#...#.#.#..#...#.#.#..#...X.#");
+        assert_eq!(map.matches('X').count(), 7);
+        assert_eq!(render(&trees, &[]), format!("{}\n", EX.trim_end()));
+    }
+
+    #[test]
+    fn far_right() {
+        let trees = parse(EX).unwrap();
+        let origin = Point::new(0, 0);
+        let far = Point::new(1_000_000_000, 1);
+        for travel in [Travel::Steps, Travel::Line].iter() {
+            let map = render(&trees, &path(&trees, origin, far, *travel).unwrap());
+            let lines = map.lines().collect::<Vec<_>>();
+            assert_eq!(lines.len(), 11);
+            assert!(lines[0].starts_with("O.##"));
+            assert!(lines
+                .iter()
+                .all(|l| l.chars().count() <= MAX_COLUMNS as usize + 1));
+            assert!(lines[1..].iter().all(|l| l.ends_with('…')));
+
+            let too_far = Point::new(i64::MAX / 4, 1);
+            assert!(path(&trees, origin, too_far, *travel).is_err());
+            assert!(path(&trees, Point::new(i64::MAX, 0), Point::new(1, 1), *travel).is_err());
+        }
+        let steep = path(&trees, origin, Point::new(1, i64::MAX), Travel::Steps).unwrap();
+        assert_eq!(steep, vec![origin]);
+        let left = path(&trees, origin, Point::new(i64::MIN / 16, 1), Travel::Steps).unwrap();
+        assert_eq!(render(&trees, &left).lines().count(), 11);
+    }
+
+    #[test]
+    fn search_slopes() {
+        let trees = parse(EX).unwrap();
+        let origin = Point::new(0, 0);
+        assert_eq!(
+            best_slope(&trees, origin, Point::new(3, 1), Travel::Steps).unwrap(),
+            Some((Point::new(2, 1), 1))
+        );
+        let (slope, hit) = best_slope(&trees, origin, Point::new(7, 2), Travel::Line)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            hit,
+            trees_on(&trees, &path(&trees, origin, slope, Travel::Line).unwrap())
+        );
+        assert_eq!(
+            best_slope(&trees, origin, Point::new(3, 0), Travel::Steps).unwrap(),
+            None
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::parsing::{split_once, ParseError};
 use anyhow::{Context, Result};
 use std::fmt;
 
@@ -19,6 +20,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
         .count())
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 #[derive(Debug)]
 struct Year(String);
 #[derive(Debug)]
@@ -86,10 +91,11 @@ fn valid_height(hgt: &Option<Height>) -> Result<()> {
         anyhow::bail!("no height")
     };
 
-    let value = s[..s.len() - 2]
+    let unit_start = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let value = s[..unit_start]
         .parse::<u32>()
         .context("height did not contain numeric value")?;
-    match &s[s.len() - 2..] {
+    match &s[unit_start..] {
         "cm" => {
             if value < 150 || value > 193 {
                 anyhow::bail!("invalid hight in cm: {}", value);
@@ -168,26 +174,22 @@ fn parse(input: &str) -> Result<Vec<PassportBuilder>> {
     let mut passports = Vec::new();
     let mut builder = PassportBuilder::default();
 
-    for line in input.lines() {
+    for (idx, line) in input.lines().enumerate() {
         if line.is_empty() {
             let mut next = PassportBuilder::default();
             std::mem::swap(&mut next, &mut builder);
             passports.push(next);
         } else {
-            parse_kv(line, &mut builder).with_context(|| format!("failed to parse: {:?}", line))?;
+            parse_kv(line, &mut builder).map_err(|e| e.on_line(idx + 1))?;
         }
     }
     passports.push(builder);
     Ok(passports)
 }
 
-fn parse_kv(s: &str, builder: &mut PassportBuilder) -> Result<()> {
+fn parse_kv(s: &str, builder: &mut PassportBuilder) -> Result<(), ParseError> {
     for pair in s.split_ascii_whitespace() {
-        let mut split = pair.splitn(2, ':').into_iter();
-        let k = split.next().ok_or_else(|| anyhow::anyhow!("kv is empty"))?;
-        let v = split
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("kv did not have ':'"))?;
+        let (k, v) = split_once(s, pair, ":")?;
         match k {
             "byr" => builder.byr = Some(Year(v.to_string())),
             "iyr" => builder.iyr = Some(Year(v.to_string())),
@@ -197,7 +199,7 @@ fn parse_kv(s: &str, builder: &mut PassportBuilder) -> Result<()> {
             "ecl" => builder.ecl = Some(Color(v.to_string())),
             "pid" => builder.pid = Some(Id(v.to_string())),
             "cid" => builder.cid = Some(Id(v.to_string())),
-            _ => anyhow::bail!("unknown key: {:?}", k),
+            _ => return Err(ParseError::at(s, k, "a passport field")),
         }
     }
     Ok(())
@@ -231,4 +233,13 @@ mod tests {
     fn p2_ex_invalid() {
         assert_eq!(format!("{}", part2(EX_INVALID).unwrap()), "0")
     }
+    #[test]
+    fn malformed_field() {
+        let e = parse("byr:1937 iyr:2017\n\nhcl #fffffd\n").unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (3, 4..4));
+        // a height too short to have a unit is invalid, not a panic
+        let pb = parse("hgt:1").unwrap();
+        assert!(valid_height(&pb[0].hgt).is_err());
+    }
 }
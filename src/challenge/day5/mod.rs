@@ -1,3 +1,4 @@
+use crate::parsing::{parse_lines, ParseError};
 use anyhow::Result;
 use std::{cmp, fmt};
 
@@ -33,6 +34,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(expected_sum - sum)
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 fn gauss_sum(n1: u32, n2: u32) -> u32 {
     (n2 * (n2 + 1) - (n1 * (n1 + 1))) / 2
 }
@@ -50,37 +55,39 @@ impl Seat {
 }
 
 fn parse(input: &str) -> Result<Vec<Seat>> {
-    input.lines().map(|pass| parse_seat(pass)).collect()
+    Ok(parse_lines(input, parse_seat)?)
 }
 
-fn parse_seat(text: &str) -> Result<Seat> {
+fn parse_seat(text: &str) -> Result<Seat, ParseError> {
     let mut row = 0u8;
     let mut column = 0u8;
-    if text.len() != 10 {
-        anyhow::bail!("invalid boarding pass length")
+    let mut chars = text.char_indices();
+    for (idx, (pos, c)) in chars.by_ref().take(ROW_SIZE + COL_SIZE).enumerate() {
+        match c {
+            'F' if idx < ROW_SIZE => {}
+            'B' if idx < ROW_SIZE => row |= 1u8 << (ROW_SIZE - 1 - idx),
+            'L' if idx >= ROW_SIZE => {}
+            'R' if idx >= ROW_SIZE => column |= 1u8 << (ROW_SIZE + COL_SIZE - 1 - idx),
+            _ => {
+                let expected = if idx < ROW_SIZE {
+                    "'F' or 'B'"
+                } else {
+                    "'L' or 'R'"
+                };
+                let end = pos + c.len_utf8();
+                return Err(ParseError::new(text, pos..end, expected));
+            }
+        }
     }
-    for row_mask in text[0..7]
-        .chars()
-        .enumerate()
-        .filter_map(|(idx, c)| match c {
-            'F' => None,
-            'B' => Some(Ok(1u8 << (ROW_SIZE - 1 - idx))),
-            _ => Some(Err(anyhow::anyhow!("unrecognized encoding: {:?}", c))),
-        })
-    {
-        row |= row_mask?;
+    if let Some((pos, _)) = chars.next() {
+        return Err(ParseError::new(
+            text,
+            pos..text.len(),
+            "end of boarding pass",
+        ));
     }
-
-    for col_mask in text[7..]
-        .chars()
-        .enumerate()
-        .filter_map(|(idx, c)| match c {
-            'L' => None,
-            'R' => Some(Ok(1u8 << (COL_SIZE - 1 - idx))),
-            _ => Some(Err(anyhow::anyhow!("unrecognized encoding: {:?}", c))),
-        })
-    {
-        column |= col_mask?;
+    if text.chars().count() < ROW_SIZE + COL_SIZE {
+        return Err(ParseError::after(text, text, "more of the boarding pass"));
     }
 
     Ok(Seat { row, column })
@@ -119,4 +126,13 @@ mod tests {
         test_single_pass("FFFBBBFRRR", 14, 7, 119);
         test_single_pass("BBFFBBFRLL", 102, 4, 820);
     }
+
+    #[test]
+    fn malformed_passes() {
+        assert_eq!(parse_seat("FBFBBFFRLX").unwrap_err().columns(), 10..11);
+        assert_eq!(parse_seat("FBFLBFFRLR").unwrap_err().columns(), 4..5);
+        assert_eq!(parse_seat("FBFBBF").unwrap_err().columns(), 7..7);
+        assert_eq!(parse_seat("FBFBBFFRLRR").unwrap_err().columns(), 11..12);
+        assert!(parse_seat("FBFBéFRLR").is_err());
+    }
 }
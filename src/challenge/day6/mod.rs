@@ -17,6 +17,11 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(colletor.map(|g| g.len()).sum::<usize>())
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).for_each(|_| ());
+    Ok(())
+}
+
 struct GroupCollector<I, F> {
     inner: I,
     join: F,
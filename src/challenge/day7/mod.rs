@@ -1,5 +1,6 @@
 use self::rule_graph::{Bag, Rules};
-use anyhow::{anyhow as ah, Context, Result};
+use crate::parsing::{parse_at, split_once, ParseError};
+use anyhow::Result;
 use std::fmt;
 
 mod rule_graph;
@@ -15,55 +16,50 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     Ok(rules.total_bags(MY_BAG))
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    create_rule_tree(input).map(|_| ())
+}
+
 fn create_rule_tree<'a>(input: &'a str) -> Result<Rules<'a>> {
     let mut rules = Rules::default();
-    for l in input.lines() {
-        add_rule(&mut rules, l)?;
+    for (idx, l) in input.lines().enumerate() {
+        add_rule(&mut rules, l).map_err(|e| e.on_line(idx + 1))?;
     }
     Ok(rules)
 }
 
-fn add_rule<'a>(rules: &mut Rules<'a>, input: &'a str) -> Result<()> {
-    let mut rule_contains = input.splitn(2, " contain ").into_iter();
-    let src = rule_contains.next().ok_or_else(|| ah!("rule was empty"))?;
-    let dst = rule_contains
-        .next()
-        .ok_or_else(|| ah!("rule did not have 'contain'"))?;
-    let bag = parse_bag(src).with_context(|| format!("{:?} was not a bag", src))?;
-    add_rule_targets(rules, bag, dst).with_context(|| format!("failed to apply rule: {:?}", input))
+fn add_rule<'a>(rules: &mut Rules<'a>, line: &'a str) -> Result<(), ParseError> {
+    let (src, dst) = split_once(line, line, " contain ")?;
+    let bag = parse_bag(line, src)?;
+    add_rule_targets(rules, line, bag, dst)
 }
 
-fn parse_bag<'a>(s: &'a str) -> Result<Bag<'a>> {
+fn parse_bag<'a>(line: &str, s: &'a str) -> Result<Bag<'a>, ParseError> {
     let bag = s
         .find("bag")
-        .ok_or_else(|| ah!("bag did not contain word 'bag'"))?;
+        .ok_or_else(|| ParseError::after(line, s, "\"bag\""))?;
     let b = &s[..bag];
     Ok(Bag(b.trim()))
 }
 
-fn parse_numerical_bags<'a>(s: &'a str) -> Result<(usize, Bag<'a>)> {
-    let s = s.trim();
-    let mut rule_numeric = s.splitn(2, " ").into_iter();
-    let num = rule_numeric
-        .next()
-        .ok_or_else(|| ah!("numerical bags empty"))?;
-    let count = num
-        .parse::<usize>()
-        .with_context(|| format!("{:?} was not a number", num))?;
-    let bag_text = rule_numeric
-        .next()
-        .ok_or_else(|| ah!("numerical bags could not be split"))?;
-    let bag = parse_bag(bag_text).with_context(|| format!("{:?} was not a bag", bag_text))?;
+fn parse_numerical_bags<'a>(line: &str, s: &'a str) -> Result<(usize, Bag<'a>), ParseError> {
+    let (num, bag_text) = split_once(line, s.trim(), " ")?;
+    let count = parse_at(line, num, "a bag count")?;
+    let bag = parse_bag(line, bag_text)?;
     Ok((count, bag))
 }
 
-fn add_rule_targets<'a>(rules: &mut Rules<'a>, bag: Bag<'a>, targets: &'a str) -> Result<()> {
+fn add_rule_targets<'a>(
+    rules: &mut Rules<'a>,
+    line: &str,
+    bag: Bag<'a>,
+    targets: &'a str,
+) -> Result<(), ParseError> {
     if targets == "no other bags." {
         rules.terminal(bag);
     } else {
-        for target in targets.split(',').into_iter() {
-            let (c, b) = parse_numerical_bags(target)
-                .with_context(|| format!("failed to parse target {:?}", target))?;
+        for target in targets.split(',') {
+            let (c, b) = parse_numerical_bags(line, target)?;
             rules.insert(bag, b, c);
         }
     }
@@ -99,4 +95,12 @@ mod tests {
     fn pt2_ex2() {
         assert_eq!(format!("{}", part2(EX2).unwrap()), "126")
     }
+    #[test]
+    fn malformed_rule() {
+        let input = "faded blue bags contain no other bags.\n\
+                     dark red bags contain two shiny gold bags.\n";
+        let e = create_rule_tree(input).unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (2, 23..26));
+    }
 }
@@ -1,48 +1,47 @@
-use crate::gameconsole::{Code, Instruction, Machine, Operation};
+use crate::gameconsole::{analysis, Code, History, Machine, RunResult, Trace};
 use anyhow::Result;
-use std::{collections::HashSet, fmt};
+use std::fmt;
+
+const TRACE_LIMIT: usize = 20;
 
 pub fn part1(input: &str) -> Result<impl fmt::Display> {
     let mut vm = Machine::new(Code::parse(input)?);
     log::trace!("{:#?}", vm);
-    let mut seen = HashSet::new();
-    while seen.insert(vm.ix) {
-        vm.step()
+    if log::log_enabled!(log::Level::Debug) {
+        vm.trace = Some(Trace::with_limit(TRACE_LIMIT));
+        vm.history = Some(History::default());
+    }
+    let result = vm.run();
+    if let Some(trace) = &vm.trace {
+        log::debug!("{:?}, last instructions run:\n{}", result, trace);
+    }
+    if let Some(path) = vm.loop_path() {
+        log::debug!("loop path: {:?}", path);
+    }
+    match result {
+        RunResult::Loop { .. } => Ok(vm.acc),
+        r => anyhow::bail!("program did not loop: {:?}", r),
     }
-    Ok(vm.acc)
 }
 pub fn part2(input: &str) -> Result<impl fmt::Display> {
-    let code = Code::parse(input)?;
-    for (ix, instr) in code.data.iter().enumerate() {
-        let new_op = match instr.op {
-            Operation::Nop => Operation::Jmp,
-            Operation::Acc => continue,
-            Operation::Jmp => Operation::Nop,
-        };
-        let mut new_code = code.clone();
-        new_code.data[ix] = Instruction {
-            op: new_op,
-            arg: instr.arg,
-        };
-        let vm = Machine::new(new_code);
-        if let Ok(acc) = check_terminate(vm) {
-            return Ok(acc);
-        }
-    }
-    anyhow::bail!("could not find suitible instruction to replace");
+    let mut code = Code::parse(input)?;
+    let patch = analysis::find_patch(&code)?
+        .ok_or_else(|| anyhow::anyhow!("could not find suitible instruction to replace"))?;
+    log::debug!("patch: {:?}", patch);
+    patch.apply(&mut code);
+    check_terminate(Machine::new(code))
+}
+
+pub fn parse_only(input: &str) -> Result<()> {
+    Code::parse(input).map(|_| ())
 }
 
 fn check_terminate(mut vm: Machine) -> Result<i64> {
-    let terminate = vm.code.len();
     log::trace!("{:#?}", vm);
-    let mut seen = HashSet::new();
-    while seen.insert(vm.ix) {
-        vm.step();
-        if vm.ix == terminate {
-            return Ok(vm.acc);
-        }
+    match vm.run() {
+        RunResult::Terminated => Ok(vm.acc),
+        r => anyhow::bail!("program did not terminate: {:?}", r),
     }
-    anyhow::bail!("cycle")
 }
 
 #[cfg(test)]
@@ -68,4 +67,11 @@ mod tests {
     fn pt2_ex() {
         assert_eq!(format!("{}", part2(EX).unwrap()), "8")
     }
+
+    #[test]
+    fn malformed_program() {
+        assert!(part1("jmp +5\nacc +1\n").is_err());
+        assert!(part1("acc +1\njmp -2\n").is_err());
+        assert!(part1("acc +1\nacc +1\n").is_err());
+    }
 }
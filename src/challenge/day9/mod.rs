@@ -1,4 +1,5 @@
-use anyhow::{Context, Result};
+use crate::parsing::{parse_at, parse_lines};
+use anyhow::Result;
 use std::fmt;
 
 const PREAMBLE_LEN: usize = 25;
@@ -10,6 +11,10 @@ pub fn part2(input: &str) -> Result<impl fmt::Display> {
     find_weakness(input, PREAMBLE_LEN)
 }
 
+pub fn parse_only(input: &str) -> Result<()> {
+    parse(input).map(|_| ())
+}
+
 fn parse_and_report_invalid_number(input: &str, preamble: usize) -> Result<i64> {
     let buf = parse(input)?;
     find_incorrect_element(buf.as_slice(), preamble)
@@ -58,13 +63,7 @@ fn find_weakness(input: &str, preamble: usize) -> Result<i64> {
 }
 
 fn parse(input: &str) -> Result<Vec<i64>> {
-    input
-        .lines()
-        .map(|l| {
-            l.parse::<i64>()
-                .with_context(|| format!("not a number: {:?}", l))
-        })
-        .collect()
+    Ok(parse_lines(input, |l| parse_at(l, l, "a number"))?)
 }
 
 fn find_incorrect_element(data: &[i64], preamble: usize) -> Result<i64> {
@@ -1,5 +1,21 @@
 use anyhow::{anyhow as ah, Context, Result};
-use std::{fs, io::Read, path};
+use std::{
+    fs,
+    io::{self, Read},
+    path,
+    time::Duration,
+};
+
+#[macro_use]
+mod solver;
+pub use solver::Solver;
+
+mod answers;
+mod bench;
+pub use bench::DEFAULT_BASELINE;
+mod runner;
+mod scaffold;
+mod watch;
 
 mod day1;
 mod day2;
@@ -19,65 +35,321 @@ mod day14;
 mod day15;
 mod day16;
 
-fn f<T: std::fmt::Display>(t: Result<T>) -> Result<()> {
-    t.map(|t| {
-        println!("{}", t);
-    })
+static SOLVERS: &[&dyn Solver] = &[
+    day!(1, "Report Repair", day1),
+    day!(2, "Password Philosophy", day2),
+    day!(3, "Toboggan Trajectory", day3),
+    day!(4, "Passport Processing", day4),
+    day!(5, "Binary Boarding", day5),
+    day!(6, "Custom Customs", day6),
+    day!(7, "Handy Haversacks", day7),
+    day!(8, "Handheld Halting", day8),
+    day!(9, "Encoding Error", day9),
+    day!(10, "Adapter Array", day10),
+    day!(11, "Seating System", day11),
+    day!(12, "Rain Risk", day12),
+    day!(13, "Shuttle Search", day13),
+    day!(14, "Docking Data", day14, stub_part2),
+    day!(15, "Rambunctious Recitation", day15),
+    day!(16, "Ticket Translation", day16, stub_part2),
+];
+
+/// Every registered day, in order
+pub fn solvers() -> impl Iterator<Item = &'static dyn Solver> {
+    SOLVERS.iter().copied()
+}
+
+pub fn solver(day: u32) -> Option<&'static dyn Solver> {
+    solvers().find(|s| s.day() == day)
 }
 
 pub fn run(args: &clap::ArgMatches) -> Result<()> {
     let day = args.value_of("day").unwrap().parse::<u32>()?;
     let part = args.value_of("part").unwrap().parse::<u32>()?;
-    let input = if args.is_present("raw") {
-        args.value_of("input").unwrap().to_string()
+    let format = output_format(args)?;
+    let (input, input_name) = if args.is_present("raw") {
+        (Ok(args.value_of("input").unwrap().to_string()), None)
+    } else {
+        let path = args.value_of("input").unwrap();
+        let input_name = if path == STDIN {
+            None
+        } else {
+            answers::input_name(path)
+        };
+        (read_to_string(path), input_name)
+    };
+    let mut result = run_day(day, part, input, input_name);
+    log::info!("day {} part {} took {:?}", day, part, result.elapsed);
+    if let Some(answers) = load_answers(args)? {
+        result.check(&answers);
+    }
+
+    match format {
+        runner::Format::Json => runner::print_json(std::slice::from_ref(&result)),
+        runner::Format::Text => match (&result.answer, &result.verdict) {
+            (Ok(answer), Some(verdict)) => println!("{} {}", answer, verdict),
+            (Ok(answer), None) => println!("{}", answer),
+            (Err(_), _) => {}
+        },
+    }
+    if let Some(answers::Verdict::Fail { expected }) = &result.verdict {
+        anyhow::bail!("expected {} but got {}", expected, result.answer?);
+    }
+    result.answer.map(|_| ())
+}
+
+/// Run a part, an unknown day is still a result so it is reported in the requested format
+fn run_day(
+    day: u32,
+    part: u32,
+    input: Result<String>,
+    input_name: Option<String>,
+) -> runner::PartResult {
+    match (solver(day), input) {
+        (None, _) => runner::PartResult::failed(
+            day,
+            part,
+            input_name,
+            ah!("unimplemented challenge day {}", day),
+        ),
+        (Some(solver), Ok(input)) => {
+            log::debug!("running day {}:{} ({})", day, part, solver.title());
+            if !solver.has_part(part) {
+                log::warn!("day {} part {} is not solved yet", day, part);
+            }
+            runner::run_part(solver, part, &input, input_name)
+        }
+        (Some(_), Err(e)) => runner::PartResult::failed(day, part, input_name, e),
+    }
+}
+
+pub fn run_all(args: &clap::ArgMatches) -> Result<()> {
+    let input_dir = args.value_of("input_dir").unwrap();
+    let format = output_format(args)?;
+    let mut results = runner::run_all(input_dir);
+    if let Some(answers) = load_answers(args)? {
+        results.iter_mut().for_each(|r| r.check(&answers));
+    }
+    match format {
+        runner::Format::Json => runner::print_json(&results),
+        runner::Format::Text => runner::print_table(&results),
+    }
+    runner::check_errors(&results)
+}
+
+pub fn run_bench(args: &clap::ArgMatches) -> Result<()> {
+    let day = args.value_of("day").unwrap().parse::<u32>()?;
+    let part = args.value_of("part").unwrap().parse::<u32>()?;
+    let iterations = args.value_of("iterations").unwrap().parse::<usize>()?;
+    let warmup = args.value_of("warmup").unwrap().parse::<usize>()?;
+    let input = match args.value_of("input") {
+        Some(p) => read_to_string(p)?,
+        None => read_to_string(format!("input/day{}", day))?,
+    };
+    let solver = solver(day).ok_or_else(|| ah!("unimplemented challenge day {}", day))?;
+    let baseline_path = args.value_of("baseline").unwrap();
+    let mut baselines = bench::Baselines::load(baseline_path)?;
+    let mut report = bench::bench(solver, part, &input, warmup, iterations)?;
+    report.baseline = baselines.get(day, part);
+    println!("{}", report);
+    if !args.is_present("no_save") {
+        baselines.update(&report);
+        baselines.save(baseline_path)?;
+    }
+    Ok(())
+}
+
+pub fn run_new(args: &clap::ArgMatches) -> Result<()> {
+    let day = args.value_of("day").unwrap().parse::<u32>()?;
+    let title = match args.value_of("title") {
+        Some(t) => t.to_string(),
+        None => format!("Day {}", day),
+    };
+    let root = path::Path::new(args.value_of("root").unwrap());
+    scaffold::scaffold(root, day, &title)
+}
+
+pub fn run_watch(args: &clap::ArgMatches) -> Result<()> {
+    let day = args.value_of("day").unwrap().parse::<u32>()?;
+    let solver = solver(day).ok_or_else(|| ah!("unimplemented challenge day {}", day))?;
+    let parts = match args.values_of("part") {
+        Some(parts) => parts
+            .map(|p| p.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()?,
+        None => solver.parts().to_vec(),
+    };
+    let mut paths = vec![path::PathBuf::from(
+        args.value_of("input")
+            .map(|p| p.to_string())
+            .unwrap_or_else(|| format!("input/day{}", day)),
+    )];
+    if let Some(example) = args.value_of("example") {
+        paths.push(example.into());
+    }
+    let debounce = Duration::from_millis(args.value_of("debounce").unwrap().parse::<u64>()?);
+    let answers = load_answers(args)?;
+    watch::watch(solver, &parts, &paths, debounce, answers.as_ref())
+}
+
+pub fn run_expenses(args: &clap::ArgMatches) -> Result<()> {
+    let k = args.value_of("k").unwrap().parse::<usize>()?;
+    let target = args.value_of("target").unwrap().parse::<i64>()?;
+    let input = read_to_string(args.value_of("input").unwrap())?;
+    println!(
+        "{}",
+        day1::report(&input, k, target, args.is_present("all"))?
+    );
+    Ok(())
+}
+
+pub fn run_passwords(args: &clap::ArgMatches) -> Result<()> {
+    if args.is_present("stream") {
+        if args.occurrences_of("policy") > 0 {
+            anyhow::bail!("--stream only counts the count and position policies");
+        }
+        let counts = day2::count_stream(open(args.value_of("input").unwrap())?)?;
+        println!(
+            "{} entries, {} follow count, {} follow position",
+            counts.entries, counts.count, counts.position
+        );
+        return Ok(());
+    }
+    let policies = args
+        .values_of("policy")
+        .unwrap()
+        .map(day2::parse_policy)
+        .collect::<Result<Vec<_>>>()?;
+    let input = read_to_string(args.value_of("input").unwrap())?;
+    println!("{}", day2::report(&input, &policies)?);
+    Ok(())
+}
+
+pub fn run_toboggan(args: &clap::ArgMatches) -> Result<()> {
+    let right = args.value_of("right").unwrap().parse::<i64>()?;
+    let down = args.value_of("down").unwrap().parse::<i64>()?;
+    let travel = if args.is_present("line") {
+        day3::Travel::Line
     } else {
-        read_to_string(args.value_of("input").unwrap())?
+        day3::Travel::Steps
     };
-    log::debug!("running day {}:{}", day, part);
-    match (day, part) {
-        (1, 1) => f(day1::part1(&input)),
-        (1, 2) => f(day1::part2(&input)),
-        (2, 1) => f(day2::part1(&input)),
-        (2, 2) => f(day2::part2(&input)),
-        (3, 1) => f(day3::part1(&input)),
-        (3, 2) => f(day3::part2(&input)),
-        (4, 1) => f(day4::part1(&input)),
-        (4, 2) => f(day4::part2(&input)),
-        (5, 1) => f(day5::part1(&input)),
-        (5, 2) => f(day5::part2(&input)),
-        (6, 1) => f(day6::part1(&input)),
-        (6, 2) => f(day6::part2(&input)),
-        (7, 1) => f(day7::part1(&input)),
-        (7, 2) => f(day7::part2(&input)),
-        (8, 1) => f(day8::part1(&input)),
-        (8, 2) => f(day8::part2(&input)),
-        (9, 1) => f(day9::part1(&input)),
-        (9, 2) => f(day9::part2(&input)),
-        (10, 1) => f(day10::part1(&input)),
-        (10, 2) => f(day10::part2(&input)),
-        (11, 1) => f(day11::part1(&input)),
-        (11, 2) => f(day11::part2(&input)),
-        (12, 1) => f(day12::part1(&input)),
-        (12, 2) => f(day12::part2(&input)),
-        (13, 1) => f(day13::part1(&input)),
-        (13, 2) => f(day13::part2(&input)),
-        (14, 1) => f(day14::part1(&input)),
-        (14, 2) => f(day14::part2(&input)),
-        (15, 1) => f(day15::part1(&input)),
-        (15, 2) => f(day15::part2(&input)),
-        (16, 1) => f(day16::part1(&input)),
-        (16, 2) => f(day16::part2(&input)),
-        (d, p) => Err(ah!("unimplemented challenge day {} part {}", d, p)),
-    }
-}
-
-fn read_to_string<P: AsRef<path::Path>>(path: P) -> Result<String> {
-    log::trace!("Reading content of file: {}", path.as_ref().display());
-    let mut f = fs::File::open(&path)
-        .with_context(|| format!("Unable to open path: {}", path.as_ref().display()))?;
+    let input = read_to_string(args.value_of("input").unwrap())?;
+    println!(
+        "{}",
+        day3::report(
+            &input,
+            aoc::Point::new(right, down),
+            travel,
+            args.is_present("search")
+        )?
+    );
+    Ok(())
+}
+
+fn output_format(args: &clap::ArgMatches) -> Result<runner::Format> {
+    args.value_of("format").unwrap_or("text").parse()
+}
+
+fn load_answers(args: &clap::ArgMatches) -> Result<Option<answers::Answers>> {
+    if args.is_present("check") {
+        let path = args.value_of("answers").unwrap_or(answers::DEFAULT_ANSWERS);
+        answers::Answers::load(path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
 
+/// Input path which reads from stdin instead of a file
+const STDIN: &str = "-";
+
+pub fn read_to_string<P: AsRef<path::Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
     let mut result = String::new();
 
-    f.read_to_string(&mut result)?;
+    if path == path::Path::new(STDIN) {
+        log::trace!("Reading content of stdin");
+        io::stdin()
+            .read_to_string(&mut result)
+            .context("Unable to read stdin")?;
+        return Ok(result);
+    }
+
+    log::trace!("Reading content of file: {}", path.display());
+    let mut f =
+        fs::File::open(path).with_context(|| format!("Unable to open path: {}", path.display()))?;
+
+    f.read_to_string(&mut result)
+        .with_context(|| format!("Unable to read path: {}", path.display()))?;
     Ok(result)
 }
+
+/// Buffered reader of a file, or of stdin
+pub fn open<P: AsRef<path::Path>>(path: P) -> Result<Box<dyn io::BufRead>> {
+    let path = path.as_ref();
+    if path == path::Path::new(STDIN) {
+        log::trace!("Streaming stdin");
+        return Ok(Box::new(io::BufReader::new(io::stdin())));
+    }
+
+    log::trace!("Streaming file: {}", path.display());
+    let f =
+        fs::File::open(path).with_context(|| format!("Unable to open path: {}", path.display()))?;
+    Ok(Box::new(io::BufReader::new(f)))
+}
+
+pub fn read_bytes<P: AsRef<path::Path>>(path: P) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let mut result = Vec::new();
+
+    if path == path::Path::new(STDIN) {
+        log::trace!("Reading bytes of stdin");
+        io::stdin()
+            .read_to_end(&mut result)
+            .context("Unable to read stdin")?;
+        return Ok(result);
+    }
+
+    log::trace!("Reading bytes of file: {}", path.display());
+    fs::read(path).with_context(|| format!("Unable to read path: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_is_ordered() {
+        let days = solvers().map(|s| s.day()).collect::<Vec<_>>();
+        assert_eq!(days, (1..=16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn registry_missing_parts() {
+        let missing = solvers()
+            .flat_map(|s| {
+                (1..=2)
+                    .filter(move |p| !s.has_part(*p))
+                    .map(move |p| (s.day(), p))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(missing, vec![(14, 2), (16, 2)]);
+    }
+
+    #[test]
+    fn unknown_day_is_a_result() {
+        let r = run_day(30, 1, Ok(String::new()), None);
+        assert_eq!(
+            r.to_json(),
+            r#"{"day":30,"part":1,"answer":null,"elapsed_ms":0.0,"error":"unimplemented challenge day 30"}"#
+        );
+        let r = run_day(15, 1, Ok("0,3,6".to_string()), None);
+        assert_eq!(r.answer.unwrap(), "436");
+    }
+
+    #[test]
+    fn registry_dispatch() {
+        let s = solver(15).unwrap();
+        assert_eq!(format!("{}", s.solve(1, "0,3,6").unwrap()), "436");
+        assert!(s.solve(3, "0,3,6").is_err());
+        assert!(solver(26).is_none());
+    }
+}
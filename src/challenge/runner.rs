@@ -0,0 +1,333 @@
+use super::{
+    answers::{Answers, Verdict},
+    read_to_string, solvers, Solver,
+};
+use anyhow::{anyhow as ah, Result};
+use serde::Serialize;
+use std::{
+    path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(ah!("unrecognized output format: {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PartResult {
+    pub day: u32,
+    pub part: u32,
+    pub input_name: Option<String>,
+    pub answer: Result<String>,
+    pub elapsed: Duration,
+    pub verdict: Option<Verdict>,
+    /// The part is a stub, so it was not run
+    pub skipped: bool,
+}
+
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    day: u32,
+    part: u32,
+    answer: Option<&'a str>,
+    elapsed_ms: f64,
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    check: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    skipped: bool,
+}
+
+impl PartResult {
+    /// A part which could not be run at all, e.g. because its input was missing
+    pub fn failed(day: u32, part: u32, input_name: Option<String>, e: anyhow::Error) -> PartResult {
+        PartResult {
+            day,
+            part,
+            input_name,
+            answer: Err(e),
+            elapsed: Duration::default(),
+            verdict: None,
+            skipped: false,
+        }
+    }
+
+    /// A stubbed out part, which is listed but not run
+    pub fn skipped(day: u32, part: u32, input_name: Option<String>) -> PartResult {
+        PartResult {
+            day,
+            part,
+            input_name,
+            answer: Ok(String::new()),
+            elapsed: Duration::default(),
+            verdict: None,
+            skipped: true,
+        }
+    }
+
+    /// Compare against the known answers, errors are left without a verdict
+    pub fn check(&mut self, answers: &Answers) {
+        if self.skipped {
+            return;
+        }
+        if let Ok(answer) = &self.answer {
+            self.verdict =
+                Some(answers.check(self.day, self.part, self.input_name.as_deref(), answer));
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let (check, expected) = match &self.verdict {
+            Some(Verdict::Pass) => (Some("pass"), None),
+            Some(Verdict::Fail { expected }) => (Some("fail"), Some(expected.as_str())),
+            Some(Verdict::Unknown) => (Some("unknown"), None),
+            None => (None, None),
+        };
+        let json = JsonResult {
+            day: self.day,
+            part: self.part,
+            answer: self
+                .answer
+                .as_ref()
+                .ok()
+                .filter(|_| !self.skipped)
+                .map(|a| a.as_str()),
+            elapsed_ms: self.elapsed.as_secs_f64() * 1000.0,
+            error: self.answer.as_ref().err().map(|e| format!("{:#}", e)),
+            check,
+            expected,
+            skipped: self.skipped,
+        };
+        serde_json::to_string(&json).expect("results are always serializable")
+    }
+}
+
+pub fn run_part(
+    solver: &dyn Solver,
+    part: u32,
+    input: &str,
+    input_name: Option<String>,
+) -> PartResult {
+    let start = Instant::now();
+    let answer = solver.solve(part, input).map(|a| format!("{}", a));
+    PartResult {
+        day: solver.day(),
+        part,
+        input_name,
+        answer,
+        elapsed: start.elapsed(),
+        verdict: None,
+        skipped: false,
+    }
+}
+
+/// Run both parts of every registered day, reading `input_dir/dayN` for each. Stubbed out
+/// parts are listed as skipped.
+pub fn run_all<P: AsRef<path::Path>>(input_dir: P) -> Vec<PartResult> {
+    let mut results = Vec::new();
+    for solver in solvers() {
+        let input_path = input_dir.as_ref().join(format!("day{}", solver.day()));
+        let input_name = Some(format!("day{}", solver.day()));
+        let input = read_to_string(&input_path);
+        for part in 1..=2 {
+            let r = match &input {
+                _ if !solver.has_part(part) => {
+                    PartResult::skipped(solver.day(), part, input_name.clone())
+                }
+                Ok(input) => run_part(solver, part, input, input_name.clone()),
+                Err(e) => {
+                    PartResult::failed(solver.day(), part, input_name.clone(), ah!("{:#}", e))
+                }
+            };
+            log::debug!("{:?}", r);
+            results.push(r);
+        }
+    }
+    results
+}
+
+pub fn print_table(results: &[PartResult]) {
+    let answers = results
+        .iter()
+        .map(|r| match &r.answer {
+            _ if r.skipped => "skipped (stub)".to_string(),
+            Ok(a) => a.clone(),
+            Err(e) => format!("error: {:#}", e),
+        })
+        .collect::<Vec<_>>();
+    let width = answers
+        .iter()
+        .map(|a| a.len())
+        .chain(std::iter::once("answer".len()))
+        .max()
+        .unwrap_or(0);
+    let checked = results.iter().any(|r| r.verdict.is_some());
+    let check_header = if checked { "check" } else { "" };
+    println!(
+        "{:>3} {:>4}  {:<width$}  {:>10}  {}",
+        "day",
+        "part",
+        "answer",
+        "elapsed",
+        check_header,
+        width = width
+    );
+    for (r, answer) in results.iter().zip(answers) {
+        let verdict = r
+            .verdict
+            .as_ref()
+            .map(|v| v.to_string())
+            .unwrap_or_default();
+        let elapsed = if r.skipped {
+            "-".to_string()
+        } else {
+            format_duration(r.elapsed)
+        };
+        println!(
+            "{:>3} {:>4}  {:<width$}  {:>10}  {}",
+            r.day,
+            r.part,
+            answer,
+            elapsed,
+            verdict,
+            width = width
+        );
+    }
+}
+
+/// One JSON object per line, so results can be streamed into other tools
+pub fn print_json(results: &[PartResult]) {
+    for r in results {
+        println!("{}", r.to_json());
+    }
+}
+
+/// Fails if any part returned an error or did not match its known answer
+pub fn check_errors(results: &[PartResult]) -> Result<()> {
+    let failed = results.iter().filter(|r| r.answer.is_err()).count();
+    let wrong = results
+        .iter()
+        .filter(|r| matches!(r.verdict, Some(Verdict::Fail { .. })))
+        .count();
+    if failed > 0 || wrong > 0 {
+        Err(ah!(
+            "{} of {} parts failed, {} gave the wrong answer",
+            failed,
+            results.len(),
+            wrong
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn format_duration(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+/// Percent change from `before` to `now`, such as `+12.5%`
+pub fn format_change(now: Duration, before: Duration) -> String {
+    if before == Duration::default() {
+        return "n/a".to_string();
+    }
+    let change = (now.as_secs_f64() / before.as_secs_f64() - 1.0) * 100.0;
+    format!("{:+.1}%", change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_answer() {
+        let r = PartResult {
+            day: 8,
+            part: 2,
+            input_name: Some("day8_ex".to_string()),
+            answer: Ok("8".to_string()),
+            elapsed: Duration::from_micros(1500),
+            verdict: Some(Verdict::Fail {
+                expected: "7".to_string(),
+            }),
+            skipped: false,
+        };
+        assert_eq!(
+            r.to_json(),
+            r#"{"day":8,"part":2,"answer":"8","elapsed_ms":1.5,"error":null,"check":"fail","expected":"7"}"#
+        );
+    }
+
+    #[test]
+    fn json_error() {
+        let r = PartResult::failed(3, 1, None, ah!("no input"));
+        assert_eq!(
+            r.to_json(),
+            r#"{"day":3,"part":1,"answer":null,"elapsed_ms":0.0,"error":"no input"}"#
+        );
+    }
+
+    #[test]
+    fn json_skipped() {
+        let r = PartResult::skipped(16, 2, Some("day16".to_string()));
+        assert_eq!(
+            r.to_json(),
+            r#"{"day":16,"part":2,"answer":null,"elapsed_ms":0.0,"error":null,"skipped":true}"#
+        );
+    }
+
+    #[test]
+    fn run_all_in_order() {
+        let dir = std::env::temp_dir().join(format!("aoc-run-all-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("day1"), "1721\n979\n366\n299\n675\n1456\n").unwrap();
+        let results = run_all(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let parts = results.iter().map(|r| (r.day, r.part)).collect::<Vec<_>>();
+        let expected = solvers()
+            .flat_map(|s| vec![(s.day(), 1), (s.day(), 2)])
+            .collect::<Vec<_>>();
+        assert_eq!(parts, expected);
+
+        assert_eq!(results[0].answer.as_ref().unwrap(), "514579");
+        assert_eq!(results[1].answer.as_ref().unwrap(), "241861950");
+        // every other day is missing its input, except for stubs which are not run
+        let skipped = results
+            .iter()
+            .filter(|r| r.skipped)
+            .map(|r| (r.day, r.part))
+            .collect::<Vec<_>>();
+        assert_eq!(skipped, vec![(14, 2), (16, 2)]);
+        let failed = results.iter().filter(|r| r.answer.is_err()).count();
+        assert_eq!(failed, results.len() - 2 - skipped.len());
+        assert!(format!("{:#}", results[2].answer.as_ref().unwrap_err()).contains("day2"));
+
+        let e = check_errors(&results).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "{} of {} parts failed, 0 gave the wrong answer",
+                failed,
+                results.len()
+            )
+        );
+        assert!(check_errors(&results[..2]).is_ok());
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow as ah, Context, Result};
+use std::{fs, path::Path};
+
+const TEMPLATE: &str = include_str!("template/mod.rs");
+const CHALLENGE_DIR: &str = "src/challenge";
+const INPUT_DIR: &str = "input";
+
+/// Create `src/challenge/dayN` from the template, empty input files for it,
+/// and register it as a stub in `src/challenge/mod.rs`
+pub fn scaffold(root: &Path, day: u32, title: &str) -> Result<()> {
+    let module_dir = root.join(CHALLENGE_DIR).join(format!("day{}", day));
+    if module_dir.exists() {
+        anyhow::bail!("day {} already exists: {}", day, module_dir.display());
+    }
+
+    // work out the new registry first, so nothing is written if it can't be updated
+    let registry_path = root.join(CHALLENGE_DIR).join("mod.rs");
+    let registry = super::read_to_string(&registry_path)?;
+    let registry = register(&registry, day, title)
+        .with_context(|| format!("could not register day in {}", registry_path.display()))?;
+
+    fs::create_dir_all(&module_dir)
+        .with_context(|| format!("could not create {}", module_dir.display()))?;
+    let module_path = module_dir.join("mod.rs");
+    fs::write(&module_path, render_template(day))
+        .with_context(|| format!("could not write {}", module_path.display()))?;
+    log::info!("created {}", module_path.display());
+
+    let input_dir = root.join(INPUT_DIR);
+    fs::create_dir_all(&input_dir)
+        .with_context(|| format!("could not create {}", input_dir.display()))?;
+    for name in &[format!("day{}", day), format!("day{}_ex", day)] {
+        let input_path = input_dir.join(name);
+        if !input_path.exists() {
+            fs::write(&input_path, "")
+                .with_context(|| format!("could not write {}", input_path.display()))?;
+            log::info!("created {}", input_path.display());
+        }
+    }
+
+    fs::write(&registry_path, registry)
+        .with_context(|| format!("could not write {}", registry_path.display()))?;
+    log::info!("registered day {} in {}", day, registry_path.display());
+    Ok(())
+}
+
+fn render_template(day: u32) -> String {
+    TEMPLATE.replace("dayN", &format!("day{}", day))
+}
+
+/// The day number of a line which starts with `prefix` followed by the number
+fn line_day(line: &str, prefix: &str) -> Option<u32> {
+    let rest = line.trim_start().strip_prefix(prefix)?;
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// Insert `new_line` before the first line for a later day, or after the last line for any day
+fn insert_ordered(lines: &mut Vec<String>, prefix: &str, day: u32, new_line: String) -> Result<()> {
+    let days = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, l)| line_day(l, prefix).map(|d| (idx, d)))
+        .collect::<Vec<_>>();
+    if days.iter().any(|(_, d)| *d == day) {
+        anyhow::bail!("day {} is already registered", day);
+    }
+    let idx = match days.iter().find(|(_, d)| *d > day) {
+        Some((idx, _)) => *idx,
+        None => {
+            days.last()
+                .ok_or_else(|| ah!("no existing {:?} lines to add to", prefix))?
+                .0
+                + 1
+        }
+    };
+    lines.insert(idx, new_line);
+    Ok(())
+}
+
+fn register(src: &str, day: u32, title: &str) -> Result<String> {
+    let mut lines = src.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+    insert_ordered(&mut lines, "mod day", day, format!("mod day{};", day))?;
+    insert_ordered(
+        &mut lines,
+        "day!(",
+        day,
+        format!("    day!({}, {:?}, day{}, stub),", day, title, day),
+    )?;
+    let mut registry = lines.join("\n");
+    registry.push('\n');
+    Ok(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTRY: &str = "mod solver;
+
+mod day1;
+mod day2;
+
+mod day10;
+
+static SOLVERS: &[&dyn Solver] = &[
+    day!(1, \"One\", day1),
+    day!(2, \"Two\", day2),
+    day!(10, \"Ten\", day10, stub_part2),
+];
+";
+
+    #[test]
+    fn register_last() {
+        let r = register(REGISTRY, 11, "Eleven").unwrap();
+        assert!(r.contains("mod day10;\nmod day11;\n"));
+        assert!(r.contains(
+            "    day!(10, \"Ten\", day10, stub_part2),\n    day!(11, \"Eleven\", day11, stub),\n];"
+        ));
+    }
+
+    #[test]
+    fn register_between() {
+        let r = register(REGISTRY, 3, "Three \"3\"").unwrap();
+        assert!(r.contains("mod day2;\n\nmod day3;\nmod day10;\n"));
+        assert!(r.contains(
+            "    day!(2, \"Two\", day2),\n    day!(3, \"Three \\\"3\\\"\", day3, stub),\n"
+        ));
+    }
+
+    #[test]
+    fn register_twice() {
+        assert!(register(REGISTRY, 2, "Two").is_err());
+    }
+
+    #[test]
+    fn template_inputs() {
+        let t = render_template(17);
+        assert!(t.contains("include_str!(\"../../../input/day17\")"));
+        assert!(t.contains("include_str!(\"../../../input/day17_ex\")"));
+        assert!(!t.contains("dayN"));
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{anyhow as ah, Result};
+use std::fmt;
+
+pub type Answer = Box<dyn fmt::Display>;
+pub type PartFn = fn(&str) -> Result<Answer>;
+pub type ParseFn = fn(&str) -> Result<()>;
+
+/// Wrap a `dayN::partM` function so its `impl Display` answer can be stored in the registry
+macro_rules! part {
+    ($f:path) => {
+        |input: &str| -> anyhow::Result<$crate::challenge::solver::Answer> {
+            $f(input).map(|a| Box::new(a) as $crate::challenge::solver::Answer)
+        }
+    };
+}
+
+/// Register a `dayN` module
+///
+/// Parts which are still placeholders can be marked, they can be run but are not reported as
+/// solved: `stub_part2` for a day with only part 1 done, `stub` for a freshly scaffolded day.
+macro_rules! day {
+    ($day:expr, $title:expr, $module:ident) => {
+        day!(@parts $day, $title, $module, &[1, 2])
+    };
+    ($day:expr, $title:expr, $module:ident, stub_part2) => {
+        day!(@parts $day, $title, $module, &[1])
+    };
+    ($day:expr, $title:expr, $module:ident, stub) => {
+        day!(@parts $day, $title, $module, &[])
+    };
+    (@parts $day:expr, $title:expr, $module:ident, $parts:expr) => {
+        &$crate::challenge::solver::Day::new(
+            $day,
+            $title,
+            part!($module::part1),
+            part!($module::part2),
+            $module::parse_only,
+            $parts,
+        )
+    };
+}
+
+pub trait Solver: Sync {
+    fn day(&self) -> u32;
+    fn title(&self) -> &'static str;
+    fn part1(&self, input: &str) -> Result<Answer>;
+    fn part2(&self, input: &str) -> Result<Answer>;
+
+    /// Only parse the input, so parsing can be timed apart from solving
+    fn parse(&self, input: &str) -> Result<()>;
+
+    /// Parts with a real solution, stubbed out parts are not included
+    fn parts(&self) -> &'static [u32] {
+        &[1, 2]
+    }
+
+    fn has_part(&self, part: u32) -> bool {
+        self.parts().contains(&part)
+    }
+
+    fn solve(&self, part: u32, input: &str) -> Result<Answer> {
+        match part {
+            1 => self.part1(input),
+            2 => self.part2(input),
+            p => Err(ah!("day {} has no part {}", self.day(), p)),
+        }
+    }
+}
+
+/// A registered day, built from the module's `part1` and `part2` functions
+pub struct Day {
+    day: u32,
+    title: &'static str,
+    part1: PartFn,
+    part2: PartFn,
+    parse: ParseFn,
+    parts: &'static [u32],
+}
+
+impl Day {
+    pub const fn new(
+        day: u32,
+        title: &'static str,
+        part1: PartFn,
+        part2: PartFn,
+        parse: ParseFn,
+        parts: &'static [u32],
+    ) -> Day {
+        Day {
+            day,
+            title,
+            part1,
+            part2,
+            parse,
+            parts,
+        }
+    }
+}
+
+impl Solver for Day {
+    fn day(&self) -> u32 {
+        self.day
+    }
+    fn title(&self) -> &'static str {
+        self.title
+    }
+    fn part1(&self, input: &str) -> Result<Answer> {
+        (self.part1)(input)
+    }
+    fn part2(&self, input: &str) -> Result<Answer> {
+        (self.part2)(input)
+    }
+    fn parse(&self, input: &str) -> Result<()> {
+        (self.parse)(input)
+    }
+    fn parts(&self) -> &'static [u32] {
+        self.parts
+    }
+}
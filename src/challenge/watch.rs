@@ -0,0 +1,233 @@
+use super::{
+    answers::{self, Answers},
+    read_to_string,
+    runner::{self, PartResult},
+    Solver,
+};
+use anyhow::{anyhow as ah, Context, Result};
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+/// An input file being watched, and the answers and times from its last run
+struct Watched {
+    path: PathBuf,
+    name: String,
+    last: BTreeMap<u32, (String, Duration)>,
+}
+
+impl Watched {
+    fn new(path: &Path) -> Result<Watched> {
+        Ok(Watched {
+            path: normalize(path)?,
+            name: answers::input_name(path).unwrap_or_else(|| path.display().to_string()),
+            last: BTreeMap::new(),
+        })
+    }
+
+    /// Run `parts` against the current file contents, returning a line per part
+    fn run(
+        &mut self,
+        solver: &dyn Solver,
+        parts: &[u32],
+        answers: Option<&Answers>,
+    ) -> Vec<String> {
+        let input = read_to_string(&self.path);
+        parts
+            .iter()
+            .map(|part| {
+                let mut r = match &input {
+                    Ok(input) => runner::run_part(solver, *part, input, Some(self.name.clone())),
+                    Err(e) => PartResult::failed(
+                        solver.day(),
+                        *part,
+                        Some(self.name.clone()),
+                        ah!("{:#}", e),
+                    ),
+                };
+                if let Some(answers) = answers {
+                    r.check(answers);
+                }
+                let line = report(
+                    &self.name,
+                    &r,
+                    self.last.get(part).map(|(a, t)| (a.as_str(), *t)),
+                );
+                self.last.insert(*part, (answer_text(&r), r.elapsed));
+                line
+            })
+            .collect()
+    }
+}
+
+fn answer_text(r: &PartResult) -> String {
+    match &r.answer {
+        Ok(a) => a.clone(),
+        Err(e) => format!("error: {:#}", e),
+    }
+}
+
+/// Describe a result relative to the answer and time of the previous run, if there was one
+fn report(name: &str, r: &PartResult, previous: Option<(&str, Duration)>) -> String {
+    let answer = answer_text(r);
+    let change = match previous {
+        None => String::new(),
+        Some((p, _)) if p == answer => " (unchanged)".to_string(),
+        Some((p, _)) => format!(" (was {})", p),
+    };
+    let timing = match previous {
+        Some((_, before)) => format!(
+            " ({}, was {})",
+            runner::format_change(r.elapsed, before),
+            runner::format_duration(before)
+        ),
+        None => String::new(),
+    };
+    let verdict = r
+        .verdict
+        .as_ref()
+        .map(|v| format!(" {}", v))
+        .unwrap_or_default();
+    format!(
+        "[{}] part {}: {}{}{} in {}{}",
+        name,
+        r.part,
+        answer,
+        change,
+        verdict,
+        runner::format_duration(r.elapsed),
+        timing
+    )
+}
+
+/// Absolute form of a path whose file may not exist, but whose directory does
+fn normalize(path: &Path) -> Result<PathBuf> {
+    let dir = match path.parent() {
+        Some(d) if d != Path::new("") => d,
+        _ => Path::new("."),
+    };
+    let file = path
+        .file_name()
+        .ok_or_else(|| ah!("not a file: {}", path.display()))?;
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("could not find directory: {}", dir.display()))?;
+    Ok(dir.join(file))
+}
+
+/// The file which was changed by an event, if it might have new contents
+fn changed_path(event: &DebouncedEvent) -> Option<&Path> {
+    match event {
+        DebouncedEvent::Create(p) | DebouncedEvent::Write(p) | DebouncedEvent::Rename(_, p) => {
+            Some(p)
+        }
+        _ => None,
+    }
+}
+
+/// Run `parts` for each of `paths`, then again for a path whenever it changes
+pub fn watch(
+    solver: &dyn Solver,
+    parts: &[u32],
+    paths: &[PathBuf],
+    debounce: Duration,
+    answers: Option<&Answers>,
+) -> Result<()> {
+    let mut watched = paths
+        .iter()
+        .map(|p| Watched::new(p))
+        .collect::<Result<Vec<_>>>()?;
+    for w in &mut watched {
+        w.run(solver, parts, answers)
+            .iter()
+            .for_each(|l| println!("{}", l));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, debounce).context("could not start file watcher")?;
+    // editors often replace a file rather than writing to it, so watch the directories instead
+    let dirs = watched
+        .iter()
+        .filter_map(|w| w.path.parent())
+        .collect::<BTreeSet<_>>();
+    for dir in dirs {
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("could not watch {}", dir.display()))?;
+        log::info!("watching {}", dir.display());
+    }
+
+    loop {
+        let event = rx.recv().context("file watcher stopped")?;
+        log::trace!("{:?}", event);
+        let changed = match changed_path(&event).map(normalize) {
+            Some(Ok(p)) => p,
+            _ => continue,
+        };
+        for w in watched.iter_mut().filter(|w| w.path == changed) {
+            w.run(solver, parts, answers)
+                .iter()
+                .for_each(|l| println!("{}", l));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(part: u32, answer: Result<String>) -> PartResult {
+        PartResult {
+            day: 12,
+            part,
+            input_name: Some("day12_ex".to_string()),
+            answer,
+            elapsed: Duration::from_micros(1500),
+            verdict: None,
+            skipped: false,
+        }
+    }
+
+    #[test]
+    fn report_changes() {
+        let r = result(1, Ok("25".to_string()));
+        assert_eq!(
+            report("day12_ex", &r, None),
+            "[day12_ex] part 1: 25 in 1.500ms"
+        );
+        assert_eq!(
+            report("day12_ex", &r, Some(("25", Duration::from_millis(1)))),
+            "[day12_ex] part 1: 25 (unchanged) in 1.500ms (+50.0%, was 1.000ms)"
+        );
+        assert_eq!(
+            report(
+                "day12_ex",
+                &r,
+                Some(("error: bad input", Duration::from_millis(3)))
+            ),
+            "[day12_ex] part 1: 25 (was error: bad input) in 1.500ms (-50.0%, was 3.000ms)"
+        );
+    }
+
+    #[test]
+    fn rerun_remembers_answers() {
+        let dir = std::env::temp_dir().join(format!("aoc-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("day15_ex");
+        let solver = super::super::solver(15).unwrap();
+
+        std::fs::write(&path, "0,3,6\n").unwrap();
+        let mut w = Watched::new(&path).unwrap();
+        assert!(w.run(solver, &[1], None)[0].starts_with("[day15_ex] part 1: 436 in "));
+        let rerun = w.run(solver, &[1], None).remove(0);
+        assert!(rerun.starts_with("[day15_ex] part 1: 436 (unchanged) in "));
+        assert!(rerun.contains("%, was "), "{}", rerun);
+        std::fs::write(&path, "1,3,2\n").unwrap();
+        assert!(w.run(solver, &[1], None)[0].starts_with("[day15_ex] part 1: 1 (was 436) in "));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
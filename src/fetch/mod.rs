@@ -0,0 +1,176 @@
+use anyhow::{anyhow as ah, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+pub const DEFAULT_SESSION_FILE: &str = ".aoc-session";
+const YEAR: u32 = 2020;
+
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
+    let day = args.value_of("day").unwrap().parse::<u32>()?;
+    let input_dir = Path::new(args.value_of("input_dir").unwrap());
+    let base_url = args.value_of("base_url").unwrap();
+
+    let path = fetch(day, input_dir, base_url, || {
+        load_session(args.value_of("session"), args.value_of("session_file"))
+    })?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// The session token is taken directly if given, otherwise read from the session file
+fn load_session(session: Option<&str>, session_file: Option<&str>) -> Result<String> {
+    if let Some(s) = session {
+        return Ok(s.trim().to_string());
+    }
+    let path = session_file.unwrap_or(DEFAULT_SESSION_FILE);
+    let session = fs::read_to_string(path).with_context(|| {
+        format!(
+            "no session token given and could not read session file: {}",
+            path
+        )
+    })?;
+    Ok(session.trim().to_string())
+}
+
+pub fn input_url(base_url: &str, day: u32) -> String {
+    format!(
+        "{}/{}/day/{}/input",
+        base_url.trim_end_matches('/'),
+        YEAR,
+        day
+    )
+}
+
+/// Download the input for `day` into `input_dir/dayN`, unless that file already exists.
+/// An empty file, such as the placeholder made for a new day, is downloaded over.
+///
+/// The session is only loaded when a download is needed.
+pub fn fetch<F: FnOnce() -> Result<String>>(
+    day: u32,
+    input_dir: &Path,
+    base_url: &str,
+    session: F,
+) -> Result<PathBuf> {
+    let path = input_dir.join(format!("day{}", day));
+    let cached = fs::metadata(&path).map(|m| m.len() > 0).unwrap_or(false);
+    if cached {
+        log::info!("using cached input: {}", path.display());
+        return Ok(path);
+    }
+
+    let text = download(&input_url(base_url, day), &session()?)?;
+
+    fs::create_dir_all(input_dir)
+        .with_context(|| format!("could not create input dir: {}", input_dir.display()))?;
+    // write to the side and rename, so a failed write never looks like a cached input
+    let partial = input_dir.join(format!(".day{}.partial", day));
+    fs::write(&partial, text)
+        .with_context(|| format!("could not write input: {}", partial.display()))?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("could not write input: {}", path.display()))?;
+    log::info!("downloaded input for day {} to {}", day, path.display());
+    Ok(path)
+}
+
+fn download(url: &str, session: &str) -> Result<String> {
+    log::debug!("GET {}", url);
+    let resp = ureq::get(url)
+        .set("Cookie", &format!("session={}", session))
+        .call();
+    if let Some(e) = resp.synthetic_error() {
+        return Err(ah!("request to {} failed: {}", url, e));
+    }
+    if !resp.ok() {
+        return Err(ah!(
+            "request to {} failed with status {} {}",
+            url,
+            resp.status(),
+            resp.status_text()
+        ));
+    }
+    resp.into_string()
+        .with_context(|| format!("could not read response from {}", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Serve a single request, returning the request head for inspection
+    fn stub_server(body: &'static str) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let head = BufReader::new(stream.try_clone().unwrap())
+                .lines()
+                .map(|l| l.unwrap())
+                .take_while(|l| !l.is_empty())
+                .collect::<Vec<_>>();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            head
+        });
+        (base_url, handle)
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aoc-fetch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn url_format() {
+        assert_eq!(
+            input_url("http://localhost:8080/", 9),
+            "http://localhost:8080/2020/day/9/input"
+        );
+    }
+
+    #[test]
+    fn fetch_then_cache() {
+        let dir = scratch_dir("cache");
+        let (base_url, server) = stub_server("1\n2\n3\n");
+
+        let path = fetch(3, &dir, &base_url, || Ok("abc".to_string())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1\n2\n3\n");
+
+        let head = server.join().unwrap();
+        assert!(head[0].starts_with("GET /2020/day/3/input "));
+        assert!(head
+            .iter()
+            .any(|h| h.to_lowercase() == "cookie: session=abc"));
+
+        // the stub server is gone, so this only works from the cache
+        let cached = fetch(3, &dir, &base_url, || Err(ah!("session not needed"))).unwrap();
+        assert_eq!(cached, path);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_input_is_not_cached() {
+        let dir = scratch_dir("empty");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("day4"), "").unwrap();
+        let (base_url, server) = stub_server("4\n");
+
+        let path = fetch(4, &dir, &base_url, || Ok("abc".to_string())).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "4\n");
+        server.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,231 @@
+use super::{Code, Flow, Instruction, Machine, Operation};
+use anyhow::Result;
+use petgraph::{
+    graphmap::DiGraphMap,
+    visit::{Dfs, Reversed},
+};
+use std::collections::BTreeSet;
+
+/// The control-flow graph of a program.
+///
+/// Nodes are instruction indices, plus `len` for the end of the program. An instruction
+/// whose jump leaves the program has no successor.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    g: DiGraphMap<usize, ()>,
+    len: usize,
+}
+
+/// Replacing the instruction at `ix` makes the program terminate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Patch {
+    pub ix: usize,
+    pub instruction: Instruction,
+}
+
+impl Patch {
+    pub fn apply(&self, code: &mut Code) {
+        code.set(self.ix, self.instruction);
+    }
+}
+
+/// Where an instruction goes next, which only depends on the instruction for an
+/// unconditional operation
+fn target(ix: usize, instr: Instruction, len: usize) -> Option<usize> {
+    let mut scratch = Machine::new(Code::new(Vec::new()));
+    let target = match instr.op.exec(&mut scratch, instr.arg) {
+        Flow::Next => ix.checked_add(1)?,
+        Flow::Jump(offset) => {
+            let target = (ix as i64).checked_add(offset)?;
+            if target < 0 {
+                return None;
+            }
+            target as usize
+        }
+    };
+    if target > len {
+        None
+    } else {
+        Some(target)
+    }
+}
+
+/// The nop/jmp swap for an instruction, if it has one
+fn swapped(instr: Instruction) -> Option<Instruction> {
+    let op = if instr.op == Operation::NOP {
+        Operation::JMP
+    } else if instr.op == Operation::JMP {
+        Operation::NOP
+    } else {
+        return None;
+    };
+    Some(Instruction { op, arg: instr.arg })
+}
+
+impl Cfg {
+    pub fn new(code: &Code) -> Result<Cfg> {
+        let len = code.len();
+        let mut g = DiGraphMap::new();
+        for ix in 0..=len {
+            g.add_node(ix);
+        }
+        for (ix, instr) in code.data.iter().enumerate() {
+            if instr.op.is_conditional() {
+                anyhow::bail!(
+                    "can not analyze conditional operation {:?} at {}",
+                    instr.op,
+                    ix
+                );
+            }
+            if let Some(next) = target(ix, *instr, len) {
+                g.add_edge(ix, next, ());
+            }
+        }
+        Ok(Cfg { g, len })
+    }
+
+    /// The node for the end of the program
+    pub fn end(&self) -> usize {
+        self.len
+    }
+
+    /// Instructions run when starting from the first one, and the end if it is reached
+    pub fn reachable(&self) -> BTreeSet<usize> {
+        let mut dfs = Dfs::new(&self.g, 0);
+        let mut reached = BTreeSet::new();
+        while let Some(ix) = dfs.next(&self.g) {
+            reached.insert(ix);
+        }
+        reached
+    }
+
+    /// Instructions which lead to the end of the program, and the end itself
+    pub fn terminating(&self) -> BTreeSet<usize> {
+        let g = Reversed(&self.g);
+        let mut dfs = Dfs::new(&g, self.len);
+        let mut terminating = BTreeSet::new();
+        while let Some(ix) = dfs.next(&g) {
+            terminating.insert(ix);
+        }
+        terminating
+    }
+}
+
+/// Find the single nop/jmp swap which makes `code` terminate.
+///
+/// Only instructions which are reached can change the outcome, and a swap works when it
+/// sends the program to an instruction which terminates, so every instruction is looked at
+/// a constant number of times.
+///
+/// A program which already terminates needs no patch, which is an error rather than `None`
+/// so it can't be mistaken for a program that can't be fixed.
+pub fn find_patch(code: &Code) -> Result<Option<Patch>> {
+    let cfg = Cfg::new(code)?;
+    let terminating = cfg.terminating();
+    if terminating.contains(&0) {
+        anyhow::bail!("the program already terminates, no patch is needed");
+    }
+    let patch = cfg
+        .reachable()
+        .into_iter()
+        .filter(|ix| *ix < cfg.end())
+        .filter_map(|ix| swapped(code.data[ix]).map(|instruction| Patch { ix, instruction }))
+        .find(|p| {
+            target(p.ix, p.instruction, cfg.end())
+                .map(|t| terminating.contains(&t))
+                .unwrap_or(false)
+        });
+    Ok(patch)
+}
+
+/// Every nop/jmp swap which makes `code` terminate, found by running each one.
+///
+/// This is slow but works with any operations, so it is a check on `find_patch`.
+#[cfg(test)]
+pub fn brute_force_patches(code: &Code) -> Vec<Patch> {
+    (0..code.len())
+        .filter_map(|ix| swapped(code.data[ix]).map(|instruction| Patch { ix, instruction }))
+        .filter(|p| {
+            let mut patched = code.clone();
+            p.apply(&mut patched);
+            Machine::new(patched).run() == super::RunResult::Terminated
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::RunResult;
+    use super::*;
+
+    const EX: &str = include_str!("../../input/day8_ex");
+
+    fn successor(cfg: &Cfg, ix: usize) -> Option<usize> {
+        cfg.g.neighbors(ix).next()
+    }
+
+    fn set(xs: &[usize]) -> BTreeSet<usize> {
+        xs.iter().copied().collect()
+    }
+
+    #[test]
+    fn example_sets() {
+        let cfg = Cfg::new(&Code::parse(EX).unwrap()).unwrap();
+        assert_eq!(cfg.end(), 9);
+        assert_eq!(successor(&cfg, 2), Some(6));
+        assert_eq!(cfg.reachable(), set(&[0, 1, 2, 3, 4, 6, 7]));
+        assert_eq!(cfg.terminating(), set(&[8, 9]));
+    }
+
+    #[test]
+    fn out_of_bounds_has_no_successor() {
+        let cfg = Cfg::new(&Code::parse("jmp +2\nacc +1\njmp -5").unwrap()).unwrap();
+        assert_eq!(successor(&cfg, 0), Some(2));
+        assert_eq!(successor(&cfg, 2), None);
+        assert_eq!(cfg.reachable(), set(&[0, 2]));
+        assert_eq!(cfg.terminating(), set(&[3]));
+    }
+
+    #[test]
+    fn example_patch() {
+        let mut code = Code::parse(EX).unwrap();
+        let patch = find_patch(&code).unwrap().unwrap();
+        assert_eq!(format!("{:?}", patch.instruction), "nop -4");
+        assert_eq!(patch.ix, 7);
+        patch.apply(&mut code);
+        let mut vm = Machine::new(code);
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, 8);
+        assert_eq!(brute_force_patches(&Code::parse(EX).unwrap()), vec![patch]);
+    }
+
+    #[test]
+    fn no_patch() {
+        let code = Code::parse("jmp +0\nacc +1\njmp -2").unwrap();
+        assert_eq!(find_patch(&code).unwrap(), None);
+        assert_eq!(brute_force_patches(&code), vec![]);
+    }
+
+    #[test]
+    fn already_terminates() {
+        let code = Code::parse("nop +0\nacc +1\njmp +1").unwrap();
+        let e = find_patch(&code).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "the program already terminates, no patch is needed"
+        );
+        assert!(find_patch(&Code::new(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn overflowing_jumps() {
+        let code = Code::parse("nop +9223372036854775807\njmp -9223372036854775808").unwrap();
+        let cfg = Cfg::new(&code).unwrap();
+        assert_eq!(successor(&cfg, 0), Some(1));
+        assert_eq!(successor(&cfg, 1), None);
+        assert_eq!(target(1, swapped(code.data[0]).unwrap(), 2), None);
+        let patch = find_patch(&code).unwrap().unwrap();
+        assert_eq!(patch.ix, 1);
+        assert_eq!(brute_force_patches(&code), vec![patch]);
+    }
+}
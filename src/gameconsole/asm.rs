@@ -0,0 +1,323 @@
+use super::{
+    generate, is_bytecode, optimize, Code, Instruction, InstructionSet, LoopPath, Operation,
+};
+use crate::parsing::{parse_at, split_once, ParseError};
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Write as _},
+};
+
+/// Assembled code, and the address of each label in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub code: Code,
+    pub labels: BTreeMap<String, usize>,
+}
+
+/// A source line split into its parts, with the comment removed
+struct SourceLine<'a> {
+    text: &'a str,
+    label: Option<&'a str>,
+    instruction: Option<(&'a str, &'a str)>,
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_line(text: &str) -> Result<SourceLine<'_>, ParseError> {
+    let code = match text.find(';') {
+        Some(end) => &text[..end],
+        None => text,
+    };
+    let (label, body) = match code.find(':') {
+        Some(end) => {
+            let label = code[..end].trim();
+            if !is_label(label) {
+                return Err(ParseError::at(text, label, "a label"));
+            }
+            (Some(label), &code[end + 1..])
+        }
+        None => (None, code),
+    };
+    let body = body.trim();
+    let instruction = if body.is_empty() {
+        None
+    } else {
+        let (op, arg) = split_once(text, body, " ")?;
+        Some((op, arg.trim()))
+    };
+    Ok(SourceLine {
+        text,
+        label,
+        instruction,
+    })
+}
+
+/// Assemble source which may have labels and `;` comments, such as `loop: jmp loop ; spin`.
+///
+/// A label names the address of the next instruction, and can be used in place of an
+/// argument to give the relative offset to that address.
+pub fn assemble(set: &InstructionSet, src: &str) -> Result<Assembly, ParseError> {
+    let lines = src
+        .lines()
+        .enumerate()
+        .map(|(idx, l)| split_line(l).map_err(|e| e.on_line(idx + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut labels = BTreeMap::new();
+    let mut addr = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        if let Some(label) = line.label {
+            if labels.insert(label.to_string(), addr).is_some() {
+                return Err(ParseError::at(
+                    line.text,
+                    label,
+                    "a label which is not already defined",
+                )
+                .on_line(idx + 1));
+            }
+        }
+        if line.instruction.is_some() {
+            addr += 1;
+        }
+    }
+
+    let mut data = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if let Some((op, arg)) = line.instruction {
+            let instruction = assemble_instruction(set, &labels, line.text, data.len(), op, arg)
+                .map_err(|e| e.on_line(idx + 1))?;
+            data.push(instruction);
+        }
+    }
+
+    Ok(Assembly {
+        code: Code::new(data),
+        labels,
+    })
+}
+
+fn assemble_instruction(
+    set: &InstructionSet,
+    labels: &BTreeMap<String, usize>,
+    text: &str,
+    addr: usize,
+    op: &str,
+    arg: &str,
+) -> Result<Instruction, ParseError> {
+    let op = set.parse_operation(text, op)?;
+    let arg = if is_label(arg) {
+        let target = labels
+            .get(arg)
+            .ok_or_else(|| ParseError::at(text, arg, "a defined label"))?;
+        *target as i64 - addr as i64
+    } else {
+        parse_at(text, arg, "an integer argument or a label")?
+    };
+    Ok(Instruction { op, arg })
+}
+
+/// The plain puzzle format, one instruction per line
+pub fn to_plain(code: &Code) -> String {
+    let mut out = String::new();
+    for instr in code.data.iter() {
+        writeln!(out, "{:?}", instr).unwrap();
+    }
+    out
+}
+
+/// A listing with the address of each instruction, labels, and where jumps go
+pub fn listing(asm: &Assembly) -> String {
+    let mut by_addr = BTreeMap::new();
+    for (label, addr) in &asm.labels {
+        by_addr
+            .entry(*addr)
+            .or_insert_with(Vec::new)
+            .push(label.as_str());
+    }
+    let width = asm.code.len().to_string().len().max(4);
+
+    let mut out = String::new();
+    for addr in 0..=asm.code.len() {
+        for label in by_addr.get(&addr).into_iter().flatten() {
+            writeln!(out, "{:width$}  {}:", "", label, width = width).unwrap();
+        }
+        let instr = match asm.code.data.get(addr) {
+            Some(instr) => instr,
+            None => break,
+        };
+        let mut line = format!("{:>width$}  {:?}", addr, instr, width = width);
+        if instr.op == Operation::JMP {
+            line = match (addr as i64).checked_add(instr.arg) {
+                Some(target) => {
+                    let name = match by_addr.get(&(target as usize)) {
+                        Some(labels) if target >= 0 => format!(" {}", labels[0]),
+                        _ => String::new(),
+                    };
+                    format!("{:<24}; -> {}{}", line, target, name)
+                }
+                None => format!("{:<24}; -> out of range", line),
+            };
+        }
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+    out
+}
+
+/// Load a program from bytecode, or from source in the plain or assembly format
+pub fn load(set: &InstructionSet, path: &str) -> Result<Code> {
+    let bytes = crate::challenge::read_bytes(path)?;
+    if is_bytecode(&bytes) {
+        return Code::from_bytes_with(set, &bytes);
+    }
+    let src = String::from_utf8(bytes).context("source is not valid UTF-8")?;
+    Ok(assemble(set, &src)?.code)
+}
+
+/// The extended set if any of `args` has `--extended`, otherwise the default one
+pub fn instruction_set(args: &[&clap::ArgMatches]) -> InstructionSet {
+    if args.iter().any(|a| a.is_present("extended")) {
+        InstructionSet::extended()
+    } else {
+        InstructionSet::default()
+    }
+}
+
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
+    let (cmd, sub_m) = args.subcommand();
+    let sub_m = sub_m.ok_or_else(|| anyhow::anyhow!("expected an asm subcommand"))?;
+    if cmd == "generate" {
+        return generate::run(sub_m);
+    }
+    let path = sub_m.value_of("file").unwrap();
+    let set = instruction_set(&[args, sub_m]);
+    if cmd == "decode" {
+        print!("{}", to_plain(&load(&set, path)?));
+        return Ok(());
+    }
+    let src = crate::challenge::read_to_string(path)?;
+    let asm = assemble(&set, &src)?;
+    match cmd {
+        "check" => println!(
+            "ok: {} instructions, {} labels",
+            asm.code.len(),
+            asm.labels.len()
+        ),
+        "list" => print!("{}", listing(&asm)),
+        "plain" => print!("{}", to_plain(&asm.code)),
+        "optimize" => print!("{}", to_plain(&optimize::optimize(&asm.code)?.code)),
+        "compare" => {
+            let c = optimize::compare(&asm.code)?;
+            println!("original:  {:?}, acc {}", c.original.result, c.original.acc);
+            println!(
+                "optimized: {:?}, acc {}",
+                c.optimized.result, c.optimized.acc
+            );
+            print_path("original", &c.original_path);
+            print_path("optimized", &c.optimized_path);
+            println!("\noptimized trace, in original addresses:");
+            println!("{}", c.optimized_trace);
+            if !c.matches() {
+                anyhow::bail!("optimized code ended differently");
+            }
+        }
+        "encode" => io::stdout()
+            .write_all(&asm.code.to_bytes_with(&set)?)
+            .context("could not write bytecode")?,
+        _ => unreachable!("unknown asm subcommand {:?}", cmd),
+    }
+    Ok(())
+}
+
+/// A loop found by `compare`, with the instructions leading to it
+fn print_path(name: &str, path: &Option<LoopPath>) {
+    let path = match path {
+        Some(path) => path,
+        None => return,
+    };
+    let join = |ixs: &[usize]| {
+        ixs.iter()
+            .map(|ix| ix.to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    };
+    if !path.lead_in.is_empty() {
+        println!("{} lead-in: {}", name, join(&path.lead_in));
+    }
+    println!("{} loop: {}", name, join(&path.cycle));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EX: &str = include_str!("../../input/day8_ex");
+
+    fn asm(src: &str) -> Result<Assembly, ParseError> {
+        assemble(&InstructionSet::default(), src)
+    }
+
+    #[test]
+    fn plain_round_trip() {
+        let a = asm(EX).unwrap();
+        assert_eq!(a.code, Code::parse(EX).unwrap());
+        assert_eq!(to_plain(&a.code), EX);
+    }
+
+    #[test]
+    fn labels_and_comments() {
+        let src = "; count to two\n\
+                   start: acc +1\n\
+                   \n\
+                   loop:\n\
+                   \tacc +1   ; again\n\
+                   jmp end\n\
+                   jmp loop\n\
+                   end:\n";
+        let a = asm(src).unwrap();
+        assert_eq!(to_plain(&a.code), "acc +1\nacc +1\njmp +2\njmp -2\n");
+        assert_eq!(a.labels["start"], 0);
+        assert_eq!(a.labels["loop"], 1);
+        assert_eq!(a.labels["end"], 4);
+
+        let a = asm("loop: jmp loop ; spin").unwrap();
+        assert_eq!(to_plain(&a.code), "jmp +0\n");
+    }
+
+    #[test]
+    fn label_errors() {
+        let e = asm("jmp nowhere").unwrap_err();
+        assert_eq!((e.line(), e.columns()), (1, 5..12));
+        let e = asm("a: nop +0\na: nop +0").unwrap_err();
+        assert_eq!((e.line(), e.columns()), (2, 1..2));
+        let e = asm("nop +0\n2x: nop +0").unwrap_err();
+        assert_eq!(e.expected(), "a label");
+        let e = asm("mov +1").unwrap_err();
+        assert_eq!(e.columns(), 1..4);
+    }
+
+    #[test]
+    fn list_with_addresses() {
+        let a = asm("top: acc +3\njmp top\njmp +5").unwrap();
+        assert_eq!(
+            listing(&a),
+            "      top:\n   0  acc +3\n   1  jmp -1            ; -> 0 top\n   2  jmp +5            ; -> 7\n"
+        );
+    }
+
+    #[test]
+    fn extreme_arguments() {
+        let src = "nop +0\njmp +9223372036854775807\nacc -9223372036854775808\n";
+        let a = asm(src).unwrap();
+        assert_eq!(to_plain(&a.code), src);
+        assert_eq!(
+            listing(&a).lines().nth(1).unwrap(),
+            "   1  jmp +9223372036854775807; -> out of range"
+        );
+    }
+}
@@ -0,0 +1,207 @@
+use super::{Code, Instruction, InstructionSet};
+use anyhow::{Context, Result};
+
+const MAGIC: &[u8] = b"GCBC";
+const VERSION: u8 = 1;
+
+/// Whether `bytes` looks like bytecode rather than text
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+/// Reads bytes in order, errors give the offset they happened at
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        let b = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of bytecode at byte {}", self.pos))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let start = self.pos;
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            let bits = u64::from(b & 0x7f);
+            if shift == 63 && bits > 1 {
+                break;
+            }
+            n |= bits << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        anyhow::bail!("varint at byte {} does not fit in 64 bits", start)
+    }
+}
+
+impl Code {
+    #[cfg(test)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.to_bytes_with(&InstructionSet::default())
+    }
+
+    /// Encode using the opcodes of `set`, which has to have every operation in the program.
+    ///
+    /// The header is the magic bytes `GCBC`, a version byte and the number of instructions as
+    /// a varint. Each instruction is then its opcode, the index of its operation in the set,
+    /// followed by its argument as a zigzag encoded varint.
+    pub fn to_bytes_with(&self, set: &InstructionSet) -> Result<Vec<u8>> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        write_varint(&mut out, self.len() as u64);
+        for (ix, instr) in self.data.iter().enumerate() {
+            let opcode = set
+                .opcode(instr.op)
+                .with_context(|| format!("operation {:?} at {} is not in the set", instr.op, ix))?;
+            out.push(opcode);
+            write_varint(&mut out, zigzag(instr.arg));
+        }
+        Ok(out)
+    }
+
+    #[cfg(test)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Code> {
+        Code::from_bytes_with(&InstructionSet::default(), bytes)
+    }
+
+    /// Decode bytecode written with the same instruction set
+    pub fn from_bytes_with(set: &InstructionSet, bytes: &[u8]) -> Result<Code> {
+        if !is_bytecode(bytes) {
+            anyhow::bail!("not bytecode, expected it to start with {:?}", "GCBC");
+        }
+        let mut r = Reader {
+            bytes,
+            pos: MAGIC.len(),
+        };
+        let version = r.byte()?;
+        if version != VERSION {
+            anyhow::bail!(
+                "unsupported bytecode version {}, expected {}",
+                version,
+                VERSION
+            );
+        }
+        let len = r.varint()?;
+        // every instruction takes at least two bytes, so a bad length can't allocate much
+        let mut data = Vec::with_capacity((len as usize).min(bytes.len() / 2));
+        for _ in 0..len {
+            let pos = r.pos;
+            let opcode = r.byte()?;
+            let op = set
+                .by_opcode(opcode)
+                .with_context(|| format!("unknown opcode {} at byte {}", opcode, pos))?;
+            let arg = unzigzag(r.varint()?);
+            data.push(Instruction { op, arg });
+        }
+        if r.pos != bytes.len() {
+            anyhow::bail!(
+                "{} bytes left over after the last instruction",
+                bytes.len() - r.pos
+            );
+        }
+        Ok(Code::new(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{asm, Flow, Operation};
+    use super::*;
+
+    const EX: &str = include_str!("../../input/day8_ex");
+
+    #[test]
+    fn varints() {
+        for n in &[0, 1, -1, 63, -64, 64, 300, -300, i64::MAX, i64::MIN] {
+            let mut out = Vec::new();
+            write_varint(&mut out, zigzag(*n));
+            let mut r = Reader {
+                bytes: &out,
+                pos: 0,
+            };
+            assert_eq!(unzigzag(r.varint().unwrap()), *n);
+            assert_eq!(r.pos, out.len());
+        }
+        let mut out = Vec::new();
+        write_varint(&mut out, 300);
+        assert_eq!(out, vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = Code::parse(EX).unwrap();
+        let bytes = code.to_bytes().unwrap();
+        assert_eq!(&bytes[..7], b"GCBC\x01\x09\x00");
+        assert_eq!(bytes.len(), 6 + 2 * 9 + 1);
+        let decoded = Code::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, code);
+        assert_eq!(asm::to_plain(&decoded), EX);
+
+        let big = Code::parse("acc +1000000\njmp -70000").unwrap();
+        assert_eq!(Code::from_bytes(&big.to_bytes().unwrap()).unwrap(), big);
+    }
+
+    #[test]
+    fn custom_set() {
+        const MUL: Operation = Operation::new("mul", |m, arg| {
+            m.acc = m.acc.wrapping_mul(arg);
+            Flow::Next
+        });
+        let mut set = InstructionSet::default();
+        set.register(MUL).unwrap();
+        let code = Code::parse_with(&set, "acc +2\nmul -3").unwrap();
+        assert!(code.to_bytes().is_err());
+        let bytes = code.to_bytes_with(&set).unwrap();
+        assert_eq!(bytes[8], 3);
+        assert_eq!(Code::from_bytes_with(&set, &bytes).unwrap(), code);
+        let e = Code::from_bytes(&bytes).unwrap_err();
+        assert_eq!(e.to_string(), "unknown opcode 3 at byte 8");
+    }
+
+    #[test]
+    fn malformed() {
+        let bytes = Code::parse(EX).unwrap().to_bytes().unwrap();
+        let e = |b: &[u8]| Code::from_bytes(b).unwrap_err().to_string();
+        assert_eq!(
+            e(b"nop +0\n"),
+            "not bytecode, expected it to start with \"GCBC\""
+        );
+        assert_eq!(e(b"GCBC\x02"), "unsupported bytecode version 2, expected 1");
+        assert_eq!(
+            e(&bytes[..bytes.len() - 1]),
+            "unexpected end of bytecode at byte 24"
+        );
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert_eq!(e(&extra), "1 bytes left over after the last instruction");
+        assert_eq!(
+            e(b"GCBC\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x7f"),
+            "varint at byte 5 does not fit in 64 bits"
+        );
+    }
+}
@@ -0,0 +1,507 @@
+use super::{asm, History, Machine, RunResult, Snapshot, StepResult, Trace};
+use anyhow::{anyhow as ah, Context, Result};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+/// How many instructions `back` can undo
+const HISTORY_LIMIT: usize = 10_000;
+
+/// How many of the last instructions `trace` shows
+const TRACE_LIMIT: usize = 20;
+
+const HELP: &str = "\
+step [n]             run n instructions, default 1
+back [n]             undo the last n instructions, default 1
+continue             run until a breakpoint, watch, loop, or the end
+break <addr|op>      stop before the instruction at addr, or any op such as jmp
+delete <addr|op>     remove a breakpoint
+watch acc [op n]     stop when acc changes, or when `acc op n` holds, op is one of == != < <= > >=
+unwatch              remove all watches
+list [radius]        disassembly around ix, default radius 3
+print                show ix, acc and registers
+trace [json]         the last instructions run, including undone ones, and how often each ran
+checkpoint           save the current state
+restore <n>          go back to checkpoint n
+reset                restart the program, keeping breakpoints and watches
+quit";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Breakpoint {
+    Addr(usize),
+    Op(String),
+}
+
+impl Breakpoint {
+    fn parse(s: &str) -> Breakpoint {
+        match s.parse::<usize>() {
+            Ok(addr) => Breakpoint::Addr(addr),
+            Err(_) => Breakpoint::Op(s.to_string()),
+        }
+    }
+
+    fn hit(&self, vm: &Machine) -> bool {
+        match self {
+            Breakpoint::Addr(addr) => vm.ix == *addr,
+            Breakpoint::Op(name) => vm
+                .code
+                .data
+                .get(vm.ix)
+                .map(|i| i.op.name() == name)
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Addr(addr) => write!(f, "address {}", addr),
+            Breakpoint::Op(name) => write!(f, "op {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn parse(s: &str) -> Result<Cmp> {
+        Ok(match s {
+            "==" => Cmp::Eq,
+            "!=" => Cmp::Ne,
+            "<" => Cmp::Lt,
+            "<=" => Cmp::Le,
+            ">" => Cmp::Gt,
+            ">=" => Cmp::Ge,
+            _ => anyhow::bail!("unknown comparison: {:?}", s),
+        })
+    }
+
+    fn holds(self, a: i64, b: i64) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+impl fmt::Display for Cmp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Cmp::Eq => "==",
+            Cmp::Ne => "!=",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Changed,
+    Acc(Cmp, i64),
+}
+
+impl Watch {
+    fn hit(self, before: i64, acc: i64) -> bool {
+        match self {
+            Watch::Changed => before != acc,
+            Watch::Acc(cmp, n) => cmp.holds(acc, n),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Changed => write!(f, "acc changed"),
+            Watch::Acc(cmp, n) => write!(f, "acc {} {}", cmp, n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// An interactive session around a machine, driven by one command per line
+#[derive(Debug)]
+pub struct Debugger {
+    start: Machine,
+    vm: Machine,
+    breakpoints: BTreeSet<Breakpoint>,
+    watches: Vec<Watch>,
+    checkpoints: Vec<Snapshot>,
+}
+
+impl Debugger {
+    /// Debug `vm`, recording its history and a trace unless it already does
+    pub fn new(mut vm: Machine) -> Debugger {
+        if vm.history.is_none() {
+            vm.history = Some(History::with_limit(HISTORY_LIMIT));
+        }
+        if vm.trace.is_none() {
+            vm.trace = Some(Trace::with_limit(TRACE_LIMIT));
+        }
+        Debugger {
+            start: vm.clone(),
+            vm,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn machine(&self) -> &Machine {
+        &self.vm
+    }
+
+    /// Run commands until the input ends or `quit`, a bad command is reported and skipped
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W) -> Result<()> {
+        for line in input.lines() {
+            let line = line.context("could not read command")?;
+            match self.execute(&line, out) {
+                Ok(Control::Quit) => break,
+                Ok(Control::Continue) => {}
+                Err(e) => writeln!(out, "error: {:#}", e)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn execute<W: Write>(&mut self, line: &str, out: &mut W) -> Result<Control> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (cmd, args) = match words.split_first() {
+            Some((cmd, args)) => (*cmd, args),
+            None => return Ok(Control::Continue),
+        };
+        match (cmd, args) {
+            ("s", _) | ("step", _) => {
+                let n = match args.first() {
+                    Some(n) => n.parse::<usize>().context("step count")?,
+                    None => 1,
+                };
+                self.step(n, out)?;
+            }
+            ("back", _) => {
+                let n = match args.first() {
+                    Some(n) => n.parse::<usize>().context("back count")?,
+                    None => 1,
+                };
+                let undone = self.vm.back(n);
+                if undone < n {
+                    writeln!(out, "history only had {} instructions", undone)?;
+                }
+                self.print(out)?;
+            }
+            ("c", []) | ("continue", []) => self.cont(out)?,
+            ("b", [at]) | ("break", [at]) => {
+                let bp = Breakpoint::parse(at);
+                writeln!(out, "breakpoint at {}", bp)?;
+                self.breakpoints.insert(bp);
+            }
+            ("d", [at]) | ("delete", [at]) => {
+                let bp = Breakpoint::parse(at);
+                if !self.breakpoints.remove(&bp) {
+                    anyhow::bail!("no breakpoint at {}", bp);
+                }
+                writeln!(out, "deleted breakpoint at {}", bp)?;
+            }
+            ("w", ["acc"]) | ("watch", ["acc"]) => self.add_watch(Watch::Changed, out)?,
+            ("w", ["acc", cmp, n]) | ("watch", ["acc", cmp, n]) => {
+                let n = n.parse::<i64>().context("watch value")?;
+                self.add_watch(Watch::Acc(Cmp::parse(cmp)?, n), out)?;
+            }
+            ("unwatch", []) => {
+                self.watches.clear();
+                writeln!(out, "removed all watches")?;
+            }
+            ("l", _) | ("list", _) => {
+                let radius = match args.first() {
+                    Some(r) => r.parse::<usize>().context("list radius")?,
+                    None => 3,
+                };
+                self.list(radius, out)?;
+            }
+            ("p", []) | ("print", []) => self.print(out)?,
+            ("t", []) | ("trace", []) => writeln!(out, "{}", self.trace()?)?,
+            ("t", ["json"]) | ("trace", ["json"]) => writeln!(out, "{}", self.trace()?.to_json())?,
+            ("checkpoint", []) => {
+                writeln!(
+                    out,
+                    "checkpoint {} at ix {}",
+                    self.checkpoints.len(),
+                    self.vm.ix
+                )?;
+                self.checkpoints.push(self.vm.snapshot());
+            }
+            ("restore", [n]) => {
+                let n = n.parse::<usize>().context("checkpoint")?;
+                let snapshot = self
+                    .checkpoints
+                    .get(n)
+                    .ok_or_else(|| ah!("no checkpoint {}", n))?;
+                self.vm.restore(snapshot);
+                self.print(out)?;
+            }
+            ("reset", []) => {
+                self.vm = self.start.clone();
+                self.print(out)?;
+            }
+            ("h", []) | ("help", []) => writeln!(out, "{}", HELP)?,
+            ("q", []) | ("quit", []) => return Ok(Control::Quit),
+            _ => return Err(ah!("unknown command {:?}, try help", line.trim())),
+        }
+        Ok(Control::Continue)
+    }
+
+    fn trace(&self) -> Result<&Trace> {
+        self.vm
+            .trace
+            .as_ref()
+            .ok_or_else(|| ah!("the machine is not being traced"))
+    }
+
+    fn add_watch<W: Write>(&mut self, watch: Watch, out: &mut W) -> Result<()> {
+        writeln!(out, "watching {}", watch)?;
+        self.watches.push(watch);
+        Ok(())
+    }
+
+    fn step<W: Write>(&mut self, n: usize, out: &mut W) -> Result<()> {
+        for _ in 0..n {
+            match self.vm.step() {
+                StepResult::Continued => {}
+                StepResult::Terminated => {
+                    writeln!(out, "terminated, acc {}", self.vm.acc)?;
+                    return Ok(());
+                }
+                r => {
+                    writeln!(out, "stopped: {:?}", r)?;
+                    return Ok(());
+                }
+            }
+        }
+        self.print(out)
+    }
+
+    fn cont<W: Write>(&mut self, out: &mut W) -> Result<()> {
+        let Debugger {
+            vm,
+            breakpoints,
+            watches,
+            ..
+        } = self;
+        let mut reason = None;
+        let mut before = vm.acc;
+        let mut first = true;
+        // the first check is skipped so continuing from a breakpoint makes progress
+        let result = vm.run_until(|m| {
+            let skip = std::mem::replace(&mut first, false);
+            let bp = breakpoints.iter().find(|b| b.hit(m));
+            let watch = watches.iter().find(|w| w.hit(before, m.acc));
+            before = m.acc;
+            if skip {
+                return false;
+            }
+            reason = match (bp, watch) {
+                (Some(bp), _) => Some(format!("breakpoint at {}", bp)),
+                (None, Some(w)) => Some(format!("watch {}", w)),
+                (None, None) => None,
+            };
+            reason.is_some()
+        });
+        match result {
+            RunResult::Stopped { .. } => {
+                writeln!(out, "stopped: {}", reason.unwrap_or_default())?;
+                return self.print(out);
+            }
+            RunResult::Terminated => writeln!(out, "terminated, acc {}", self.vm.acc)?,
+            RunResult::Loop { ix } => {
+                writeln!(
+                    out,
+                    "loop: instruction {} was about to run again, acc {}",
+                    ix, self.vm.acc
+                )?;
+                if let Some(path) = self.vm.loop_path() {
+                    let join = |ixs: &[usize]| {
+                        ixs.iter()
+                            .map(|ix| ix.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" -> ")
+                    };
+                    if !path.lead_in.is_empty() {
+                        writeln!(out, "lead-in: {}", join(&path.lead_in))?;
+                    }
+                    writeln!(out, "path: {}", join(&path.cycle))?;
+                }
+            }
+            r => writeln!(out, "stopped: {:?}", r)?,
+        }
+        Ok(())
+    }
+
+    fn print<W: Write>(&self, out: &mut W) -> Result<()> {
+        let next = match self.vm.code.data.get(self.vm.ix) {
+            Some(instr) => format!("{:?}", instr),
+            None => "end".to_string(),
+        };
+        writeln!(
+            out,
+            "ix {} ({}) acc {} regs {:?}",
+            self.vm.ix, next, self.vm.acc, self.vm.regs
+        )?;
+        Ok(())
+    }
+
+    fn list<W: Write>(&self, radius: usize, out: &mut W) -> Result<()> {
+        let ix = self.vm.ix;
+        let start = ix.saturating_sub(radius);
+        let end = (ix + radius + 1).min(self.vm.code.len());
+        for addr in start..end {
+            let instr = self.vm.code.data[addr];
+            let current = if addr == ix { '>' } else { ' ' };
+            let bp = if self.breakpoints.contains(&Breakpoint::Addr(addr))
+                || self
+                    .breakpoints
+                    .contains(&Breakpoint::Op(instr.op.name().to_string()))
+            {
+                '*'
+            } else {
+                ' '
+            };
+            writeln!(out, "{}{} {:>4}  {:?}", current, bp, addr, instr)?;
+        }
+        if ix >= self.vm.code.len() {
+            writeln!(out, ">  {:>4}  end", ix)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
+    let path = args.value_of("file").unwrap();
+    let code = asm::load(&asm::instruction_set(&[args]), path)?;
+    let mut debugger = Debugger::new(Machine::new(code));
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    debugger.run(stdin.lock(), &mut stdout.lock())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Code;
+    use super::*;
+
+    const EX: &str = include_str!("../../input/day8_ex");
+
+    fn session(commands: &str) -> (Debugger, String) {
+        let mut d = Debugger::new(Machine::new(Code::parse(EX).unwrap()));
+        let mut out = Vec::new();
+        d.run(commands.as_bytes(), &mut out).unwrap();
+        (d, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn step_and_print() {
+        let (d, out) = session("step\nstep 2\nprint\n");
+        assert_eq!(d.machine().ix, 6);
+        assert_eq!(
+            out,
+            "ix 1 (acc +1) acc 0 regs [0, 0, 0, 0]\n\
+             ix 6 (acc +1) acc 1 regs [0, 0, 0, 0]\n\
+             ix 6 (acc +1) acc 1 regs [0, 0, 0, 0]\n"
+        );
+    }
+
+    #[test]
+    fn breakpoints() {
+        let (d, out) = session("break 4\ncontinue\ncontinue\ndelete 4\ncontinue\n");
+        assert_eq!(d.machine().ix, 4);
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[1], "stopped: breakpoint at address 4");
+        assert_eq!(lines[2], "ix 4 (jmp -3) acc 5 regs [0, 0, 0, 0]");
+        assert_eq!(lines[4], "ix 4 (jmp -3) acc 10 regs [0, 0, 0, 0]");
+        assert_eq!(
+            lines[6],
+            "loop: instruction 4 was about to run again, acc 15"
+        );
+
+        let (d, out) = session("break jmp\nc\nc\ndelete jmp\ndelete jmp\n");
+        assert_eq!(d.machine().ix, 7);
+        assert!(out.contains("stopped: breakpoint at op jmp\nix 2 (jmp +4)"));
+        assert!(out.ends_with("deleted breakpoint at op jmp\nerror: no breakpoint at op jmp\n"));
+    }
+
+    #[test]
+    fn watches() {
+        let (d, _) = session("watch acc >= 2\ncontinue\n");
+        assert_eq!((d.machine().ix, d.machine().acc), (7, 2));
+        let (d, _) = session("watch acc\nc\nc\n");
+        assert_eq!((d.machine().ix, d.machine().acc), (7, 2));
+        let (_, out) = session("watch acc ~ 2\nunwatch\nc\n");
+        assert!(out.starts_with("error: unknown comparison: \"~\"\n"));
+    }
+
+    #[test]
+    fn list_window() {
+        let (_, out) = session("s 3\nb 7\nlist 1\nquit\nprint\n");
+        assert!(out.ends_with("      5  acc -99\n>     6  acc +1\n *    7  jmp -4\n"));
+    }
+
+    #[test]
+    fn back_and_checkpoints() {
+        let (d, out) = session("c\nback 3\ncheckpoint\nback 10\nrestore 0\nrestore 1\n");
+        assert_eq!((d.machine().ix, d.machine().acc), (7, 2));
+        assert_eq!(
+            out,
+            "loop: instruction 1 was about to run again, acc 5\n\
+             lead-in: 0\n\
+             path: 1 -> 2 -> 6 -> 7 -> 3 -> 4 -> 1\n\
+             ix 7 (jmp -4) acc 2 regs [0, 0, 0, 0]\n\
+             checkpoint 0 at ix 7\n\
+             history only had 4 instructions\n\
+             ix 0 (nop +0) acc 0 regs [0, 0, 0, 0]\n\
+             ix 7 (jmp -4) acc 2 regs [0, 0, 0, 0]\n\
+             error: no checkpoint 1\n"
+        );
+    }
+
+    #[test]
+    fn trace_dump() {
+        let (_, out) = session("s 2\nback\ntrace\ntrace json\n");
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[2], "    ix  instruction    acc before    acc after");
+        assert_eq!(lines[4], "     1  acc +1                  0            1");
+        assert_eq!(lines[8], "     1  acc +1                  1");
+        assert!(lines[9].starts_with(r#"{"entries":[{"ix":0,"instruction":"nop +0""#));
+    }
+
+    #[test]
+    fn reset_and_errors() {
+        let (d, out) = session("s 3\nreset\nfly\nstep x\n");
+        assert_eq!(d.machine().ix, 0);
+        assert!(out.contains("error: unknown command \"fly\", try help\n"));
+        assert!(out.contains("error: step count: "));
+    }
+}
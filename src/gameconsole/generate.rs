@@ -0,0 +1,390 @@
+use super::{analysis::Patch, asm, Code, Instruction, Operation};
+use anyhow::{Context, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+/// Random programs using `nop`, `acc` and `jmp`, where every jump lands inside the program
+/// or just past its end
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub len: usize,
+    /// Chance of each instruction being a `jmp`
+    pub jmp: f64,
+    /// Chance of each instruction being a `nop`, the rest are `acc`
+    pub nop: f64,
+    /// Chance of a `jmp` or `nop` pointing backwards, which is what makes loops
+    pub loop_density: f64,
+    pub max_jump: usize,
+    pub max_acc: i64,
+}
+
+impl Default for Generator {
+    fn default() -> Generator {
+        Generator {
+            len: 100,
+            jmp: 0.3,
+            nop: 0.2,
+            loop_density: 0.3,
+            max_jump: 20,
+            max_acc: 100,
+        }
+    }
+}
+
+impl Generator {
+    fn op<R: Rng>(&self, rng: &mut R) -> Operation {
+        let x = rng.gen::<f64>();
+        if x < self.jmp {
+            Operation::JMP
+        } else if x < self.jmp + self.nop {
+            Operation::NOP
+        } else {
+            Operation::ACC
+        }
+    }
+
+    fn acc<R: Rng>(&self, rng: &mut R) -> i64 {
+        rng.gen_range(-self.max_acc..=self.max_acc)
+    }
+
+    /// An offset from `ix` to somewhere in `0..=len`
+    fn offset<R: Rng>(&self, rng: &mut R, ix: usize, len: usize) -> i64 {
+        let max_jump = self.max_jump.max(1);
+        if rng.gen_bool(self.loop_density) {
+            -(rng.gen_range(0..=ix.min(max_jump)) as i64)
+        } else {
+            rng.gen_range(1..=(len - ix).min(max_jump)) as i64
+        }
+    }
+
+    fn instruction<R: Rng>(&self, rng: &mut R, ix: usize, len: usize) -> Instruction {
+        let op = self.op(rng);
+        let arg = if op == Operation::ACC {
+            self.acc(rng)
+        } else {
+            self.offset(rng, ix, len)
+        };
+        Instruction { op, arg }
+    }
+
+    pub fn code<R: Rng>(&self, rng: &mut R) -> Code {
+        Code::new(
+            (0..self.len)
+                .map(|ix| self.instruction(rng, ix, self.len))
+                .collect(),
+        )
+    }
+
+    /// A program which loops, where exactly one nop/jmp swap makes it terminate, like the
+    /// day 8 puzzle.
+    ///
+    /// The program is built from a path of forward steps through it. One `acc` or `nop` on
+    /// the path becomes a `jmp` back to an earlier step, which is the bug. Before the bug,
+    /// every `nop` and every skipped instruction points back into the path, so no other swap
+    /// can get past it.
+    pub fn code_with_fix<R: Rng>(&self, rng: &mut R) -> (Code, Patch) {
+        let len = self.len.max(1);
+        let max_jump = self.max_jump.max(1);
+        let mut data = vec![None; len];
+        let mut path = Vec::new();
+        let mut ix = 0;
+        while ix < len {
+            path.push(ix);
+            // the path ends on the last instruction, which is never a jump so there is
+            // always somewhere to put the bug
+            let mut op = self.op(rng);
+            if ix == len - 1 && op == Operation::JMP {
+                op = Operation::NOP;
+            }
+            let arg = if op == Operation::JMP {
+                rng.gen_range(1..=(len - 1 - ix).min(max_jump)) as i64
+            } else if op == Operation::ACC {
+                self.acc(rng)
+            } else {
+                0
+            };
+            data[ix] = Some(Instruction { op, arg });
+            ix = (ix as i64 + if op == Operation::JMP { arg } else { 1 }) as usize;
+        }
+
+        let bug = *path
+            .iter()
+            .copied()
+            .filter(|ix| data[*ix].map(|i| i.op) != Some(Operation::JMP))
+            .collect::<Vec<_>>()
+            .choose(rng)
+            .expect("the last instruction is on the path and is not a jump");
+        let before_bug = path
+            .iter()
+            .copied()
+            .filter(|ix| *ix <= bug)
+            .collect::<Vec<_>>();
+        let back_into_path = |rng: &mut R, ix: usize| {
+            *before_bug.choose(rng).expect("the bug is on the path") as i64 - ix as i64
+        };
+
+        for (ix, slot) in data.iter_mut().enumerate() {
+            *slot = Some(match *slot {
+                _ if ix == bug => Instruction {
+                    op: Operation::JMP,
+                    arg: back_into_path(rng, ix),
+                },
+                Some(Instruction { op, .. }) if op == Operation::NOP => Instruction {
+                    op,
+                    arg: if ix < bug {
+                        back_into_path(rng, ix)
+                    } else {
+                        self.offset(rng, ix, len)
+                    },
+                },
+                Some(instr) => instr,
+                None if ix < bug => Instruction {
+                    op: Operation::JMP,
+                    arg: back_into_path(rng, ix),
+                },
+                None => self.instruction(rng, ix, len),
+            });
+        }
+
+        let code = Code::new(data.into_iter().map(Option::unwrap).collect());
+        let patch = Patch {
+            ix: bug,
+            instruction: Instruction {
+                op: Operation::NOP,
+                arg: code.data[bug].arg,
+            },
+        };
+        (code, patch)
+    }
+
+    /// A program from `seed` in the plain format, after `;` comments saying how to generate it
+    /// again and, with `with_fix`, which swap makes it terminate
+    pub fn program(&self, seed: u64, with_fix: bool) -> String {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut out = format!(
+            "; asm generate --len {} --loop-density {} --seed {}{}\n",
+            self.len,
+            self.loop_density,
+            seed,
+            if with_fix { " --with-fix" } else { "" }
+        );
+        let code = if with_fix {
+            let (code, patch) = self.code_with_fix(&mut rng);
+            out += &format!(
+                "; fix: {} {:?} -> {:?}\n",
+                patch.ix, code.data[patch.ix], patch.instruction
+            );
+            code
+        } else {
+            self.code(&mut rng)
+        };
+        out + &asm::to_plain(&code)
+    }
+}
+
+/// Print a random program, which `asm` and `debug` can load again
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
+    let gen = Generator {
+        len: args.value_of("len").unwrap().parse().context("len")?,
+        loop_density: args
+            .value_of("loop_density")
+            .unwrap()
+            .parse()
+            .context("loop density")?,
+        ..Generator::default()
+    };
+    if !(0.0..=1.0).contains(&gen.loop_density) {
+        anyhow::bail!(
+            "loop density has to be between 0 and 1, got {}",
+            gen.loop_density
+        );
+    }
+    let seed = match args.value_of("seed") {
+        Some(seed) => seed.parse().context("seed")?,
+        None => rand::random(),
+    };
+    print!("{}", gen.program(seed, args.is_present("with_fix")));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        analysis, asm, optimize, Flow, History, InstructionSet, Machine, RunResult, StepResult,
+        Trace, REGISTERS,
+    };
+    use super::*;
+    use proptest::prelude::*;
+
+    fn generator(len: usize, loop_density: f64) -> Generator {
+        Generator {
+            len,
+            loop_density,
+            ..Generator::default()
+        }
+    }
+
+    const MUL: Operation = Operation::new("mul", |m, arg| {
+        m.acc = m.acc.wrapping_mul(arg);
+        Flow::Next
+    });
+    const SET: Operation = Operation::new("set", |m, arg| {
+        m.regs[0] = arg;
+        Flow::Next
+    });
+    // jump if register 0 is not zero, counting it down
+    const LOOP: Operation = Operation::conditional("loop", |m, arg| {
+        if m.regs[0] == 0 {
+            return Flow::Next;
+        }
+        m.regs[0] = m.regs[0].wrapping_sub(1);
+        Flow::Jump(arg)
+    });
+
+    fn extended_set() -> InstructionSet {
+        let mut set = InstructionSet::default();
+        for op in [MUL, SET, LOOP].iter() {
+            set.register(*op).unwrap();
+        }
+        set
+    }
+
+    /// Any of `ops` with any argument, which may jump anywhere or overflow, unlike `Generator`
+    fn arbitrary_code(ops: Vec<Operation>) -> impl Strategy<Value = Code> {
+        let arg = prop_oneof![-3i64..=3, Just(i64::MIN), Just(i64::MAX), any::<i64>()];
+        prop::collection::vec((prop::sample::select(ops), arg), 0..40).prop_map(|instrs| {
+            Code::new(
+                instrs
+                    .into_iter()
+                    .map(|(op, arg)| Instruction { op, arg })
+                    .collect(),
+            )
+        })
+    }
+
+    #[test]
+    fn deterministic() {
+        let gen = Generator::default();
+        let a = gen.code(&mut StdRng::seed_from_u64(8));
+        let b = gen.code(&mut StdRng::seed_from_u64(8));
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 100);
+    }
+
+    #[test]
+    fn stored_program() {
+        let gen = generator(30, 0.4);
+        let src = gen.program(5, true);
+        assert_eq!(src, gen.program(5, true));
+        let lines = src.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "; asm generate --len 30 --loop-density 0.4 --seed 5 --with-fix"
+        );
+        assert!(lines[1].starts_with("; fix: "));
+
+        let mut code = asm::assemble(&InstructionSet::default(), &src)
+            .unwrap()
+            .code;
+        let (expected, patch) = gen.code_with_fix(&mut StdRng::seed_from_u64(5));
+        assert_eq!(code, expected);
+        patch.apply(&mut code);
+        assert_eq!(Machine::new(code).run(), RunResult::Terminated);
+
+        let plain = gen.program(5, false);
+        assert_eq!(plain.lines().count(), 31);
+        assert!(!plain.contains("--with-fix"));
+    }
+
+    proptest! {
+        #[test]
+        fn single_fix(seed: u64, len in 1usize..200, loop_density in 0.0..1.0) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let (code, patch) = generator(len, loop_density).code_with_fix(&mut rng);
+            prop_assert_eq!(code.len(), len);
+            let looped = matches!(Machine::new(code.clone()).run(), RunResult::Loop { .. });
+            prop_assert!(looped);
+            prop_assert_eq!(analysis::brute_force_patches(&code), vec![patch]);
+            prop_assert_eq!(analysis::find_patch(&code).unwrap(), Some(patch));
+        }
+
+        #[test]
+        fn solvers_agree(seed: u64, len in 1usize..200, loop_density in 0.0..1.0) {
+            let code = generator(len, loop_density).code(&mut StdRng::seed_from_u64(seed));
+            if let RunResult::Loop { .. } = Machine::new(code.clone()).run() {
+                let brute_force = analysis::brute_force_patches(&code);
+                prop_assert_eq!(analysis::find_patch(&code).unwrap(), brute_force.first().copied());
+            }
+        }
+
+        #[test]
+        fn machine_never_panics(seed: u64, len in 0usize..200, steps in 0usize..500) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let code = generator(len, 0.5).code(&mut rng);
+            Machine::new(code.clone()).run();
+
+            let mut vm = Machine::new(code.clone());
+            vm.history = Some(History::default());
+            for _ in 0..steps {
+                if vm.step() != StepResult::Continued {
+                    break;
+                }
+            }
+            vm.loop_path();
+            vm.back(steps + 1);
+            prop_assert_eq!((vm.ix, vm.acc), (0, 0));
+
+            prop_assert_eq!(Code::from_bytes(&code.to_bytes().unwrap()).unwrap(), code);
+        }
+
+        #[test]
+        fn any_program_never_panics(
+            code in arbitrary_code(vec![
+                Operation::NOP, Operation::ACC, Operation::JMP, MUL, SET, LOOP,
+            ]),
+            steps in 0usize..200,
+        ) {
+            let set = extended_set();
+            let mut vm = Machine::new(code.clone());
+            let mut n = 0;
+            vm.run_until(|_| {
+                n += 1;
+                n > 1_000
+            });
+
+            let mut vm = Machine::new(code.clone());
+            vm.history = Some(History::default());
+            vm.trace = Some(Trace::with_limit(20));
+            for _ in 0..steps {
+                if vm.step() != StepResult::Continued {
+                    break;
+                }
+            }
+            vm.loop_path();
+            vm.back(steps + 1);
+            prop_assert_eq!((vm.ix, vm.acc, vm.regs), (0, 0, [0; REGISTERS]));
+
+            let _ = optimize::compare(&code);
+            let _ = analysis::find_patch(&code);
+
+            let bytes = code.to_bytes_with(&set).unwrap();
+            prop_assert_eq!(&Code::from_bytes_with(&set, &bytes).unwrap(), &code);
+            let plain = asm::to_plain(&code);
+            prop_assert_eq!(&Code::parse_with(&set, &plain).unwrap(), &code);
+            let assembled = asm::assemble(&set, &plain).unwrap();
+            prop_assert_eq!(&assembled.code, &code);
+            asm::listing(&assembled);
+        }
+
+        #[test]
+        fn any_puzzle_program(
+            code in arbitrary_code(vec![Operation::NOP, Operation::ACC, Operation::JMP]),
+        ) {
+            let c = optimize::compare(&code).unwrap();
+            prop_assert!(c.matches(), "{:?}", c);
+            if let RunResult::Loop { .. } = Machine::new(code.clone()).run() {
+                let brute_force = analysis::brute_force_patches(&code);
+                prop_assert_eq!(analysis::find_patch(&code).unwrap(), brute_force.first().copied());
+            }
+        }
+    }
+}
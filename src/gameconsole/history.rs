@@ -0,0 +1,138 @@
+use super::{Code, REGISTERS};
+use std::collections::VecDeque;
+
+/// The state of a machine, cheap to take since the code is shared until one of them changes it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub acc: i64,
+    pub regs: [i64; REGISTERS],
+    pub ix: usize,
+    pub code: Code,
+}
+
+/// Snapshots from before each executed instruction, so they can be undone
+///
+/// Only the most recent `limit` snapshots are kept.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    limit: Option<usize>,
+}
+
+impl History {
+    /// Keep only the last `limit` snapshots
+    pub fn with_limit(limit: usize) -> History {
+        History {
+            limit: Some(limit),
+            ..History::default()
+        }
+    }
+
+    pub fn push(&mut self, snapshot: Snapshot) {
+        match self.limit {
+            Some(0) => return,
+            Some(limit) if self.snapshots.len() == limit => {
+                self.snapshots.pop_front();
+            }
+            _ => {}
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Oldest first
+    pub fn snapshots(&self) -> impl DoubleEndedIterator<Item = &Snapshot> {
+        self.snapshots.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Machine, RunResult, StepResult, Trace};
+    use super::*;
+
+    fn machine(src: &str, history: History) -> Machine {
+        let mut vm = Machine::new(Code::parse(src).unwrap());
+        vm.history = Some(history);
+        vm
+    }
+
+    #[test]
+    fn step_back() {
+        let mut vm = machine("acc +1\nacc +2\njmp -1", History::default());
+        let start = vm.snapshot();
+        vm.step();
+        let after_one = vm.snapshot();
+        vm.step();
+        vm.step();
+        assert_eq!((vm.ix, vm.acc), (1, 3));
+        assert_eq!(vm.back(2), 2);
+        assert_eq!(vm.snapshot(), after_one);
+        assert_eq!(vm.back(5), 1);
+        assert_eq!(vm.snapshot(), start);
+        assert_eq!(vm.back(1), 0);
+
+        let mut vm = machine("acc +1\nacc +2\nacc +3", History::with_limit(2));
+        vm.run();
+        assert_eq!(vm.back(3), 2);
+        assert_eq!((vm.ix, vm.acc), (1, 1));
+    }
+
+    #[test]
+    fn restore_checkpoint() {
+        let mut vm = Machine::new(Code::parse("acc +1\nacc +2\njmp -1").unwrap());
+        vm.step();
+        let checkpoint = vm.snapshot();
+        vm.code.set(0, "acc +5".parse().unwrap());
+        vm.ix = 0;
+        vm.step();
+        assert_eq!(vm.acc, 6);
+        assert_ne!(vm.code, checkpoint.code);
+        vm.restore(&checkpoint);
+        assert_eq!(vm.snapshot(), checkpoint);
+        assert_eq!(format!("{:?}", vm.code.data[0]), "acc +1");
+    }
+
+    #[test]
+    fn path_into_loop() {
+        let mut vm = machine(include_str!("../../input/day8_ex"), History::default());
+        let r = vm.run();
+        assert_eq!(r, RunResult::Loop { ix: 1 });
+        let path = vm.loop_path().unwrap();
+        assert_eq!(path.lead_in, vec![0]);
+        assert_eq!(path.cycle, vec![1, 2, 6, 7, 3, 4, 1]);
+
+        let vm = machine("jmp +0", History::default());
+        assert_eq!(vm.loop_path(), None);
+        let mut vm = machine("jmp +0", History::with_limit(0));
+        assert_eq!(vm.run(), RunResult::Loop { ix: 0 });
+        assert_eq!(vm.loop_path(), None);
+        let mut vm = machine("nop +0\njmp +1", History::default());
+        vm.run();
+        assert_eq!(vm.loop_path(), None);
+    }
+
+    #[test]
+    fn failed_jump_is_not_recorded() {
+        let mut vm = machine("acc +1\njmp -5", History::default());
+        vm.trace = Some(Trace::default());
+        assert_eq!(vm.step(), StepResult::Continued);
+        assert_eq!(vm.step(), StepResult::JumpOutOfBounds { ix: 1, target: -4 });
+        assert_eq!((vm.ix, vm.acc), (1, 1));
+        assert_eq!(vm.history.as_ref().unwrap().len(), 1);
+        assert_eq!(vm.trace.as_ref().unwrap().hits().len(), 1);
+        assert_eq!(vm.back(1), 1);
+        assert_eq!((vm.ix, vm.acc), (0, 0));
+    }
+}
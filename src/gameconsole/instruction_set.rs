@@ -0,0 +1,165 @@
+use super::{Instruction, Machine};
+use crate::parsing::{parse_at, split_once, ParseError};
+use anyhow::Result;
+use std::fmt;
+
+/// Where the machine goes after an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Next,
+    /// Jump relative to the current instruction
+    Jump(i64),
+}
+
+/// The semantics of an operation, given the machine and the instruction's argument.
+///
+/// The accumulator and registers are fixed width and wrap around on overflow, so operations
+/// should use `wrapping_*` arithmetic rather than panic on a malformed program.
+pub type Exec = fn(&mut Machine, i64) -> Flow;
+
+/// An opcode, identified by the name it is parsed and disassembled as
+#[derive(Clone, Copy)]
+pub struct Operation {
+    name: &'static str,
+    exec: Exec,
+    conditional: bool,
+}
+
+impl Operation {
+    pub const NOP: Operation = Operation::new("nop", |_, _| Flow::Next);
+    pub const ACC: Operation = Operation::new("acc", |m, arg| {
+        m.acc = m.acc.wrapping_add(arg);
+        Flow::Next
+    });
+    pub const JMP: Operation = Operation::new("jmp", |_, arg| Flow::Jump(arg));
+    /// Multiply the accumulator, only in `InstructionSet::extended`
+    pub const MUL: Operation = Operation::new("mul", |m, arg| {
+        m.acc = m.acc.wrapping_mul(arg);
+        Flow::Next
+    });
+    /// Jump unless the accumulator is zero, only in `InstructionSet::extended`
+    pub const JNZ: Operation = Operation::conditional("jnz", |m, arg| {
+        if m.acc == 0 {
+            Flow::Next
+        } else {
+            Flow::Jump(arg)
+        }
+    });
+
+    /// An operation whose flow does not depend on the state of the machine
+    pub const fn new(name: &'static str, exec: Exec) -> Operation {
+        Operation {
+            name,
+            exec,
+            conditional: false,
+        }
+    }
+
+    /// An operation which may or may not jump depending on the state of the machine
+    pub const fn conditional(name: &'static str, exec: Exec) -> Operation {
+        Operation {
+            name,
+            exec,
+            conditional: true,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        self.conditional
+    }
+
+    pub fn exec(&self, machine: &mut Machine, arg: i64) -> Flow {
+        (self.exec)(machine, arg)
+    }
+}
+
+/// Operations are told apart by name, which is unique within an `InstructionSet`, since
+/// function pointers don't compare reliably: the same function can have more than one address.
+impl PartialEq for Operation {
+    fn eq(&self, other: &Operation) -> bool {
+        self.name == other.name
+    }
+}
+impl Eq for Operation {}
+
+impl fmt::Debug for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// The operations a program may use, `nop`, `acc` and `jmp` unless more are registered
+#[derive(Debug, Clone)]
+pub struct InstructionSet {
+    ops: Vec<Operation>,
+}
+
+impl Default for InstructionSet {
+    fn default() -> InstructionSet {
+        InstructionSet {
+            ops: vec![Operation::NOP, Operation::ACC, Operation::JMP],
+        }
+    }
+}
+
+impl InstructionSet {
+    /// The default operations, then `mul` and `jnz`
+    pub fn extended() -> InstructionSet {
+        let mut set = InstructionSet::default();
+        for op in [Operation::MUL, Operation::JNZ].iter() {
+            set.register(*op)
+                .expect("extended operations have distinct names");
+        }
+        set
+    }
+
+    /// Add an operation, names must be unique within the set.
+    ///
+    /// Operations are numbered in the order they are registered, for bytecode.
+    pub fn register(&mut self, op: Operation) -> Result<()> {
+        if self.get(op.name).is_some() {
+            anyhow::bail!("operation {:?} is already registered", op.name);
+        }
+        if self.ops.len() > u8::MAX as usize {
+            anyhow::bail!("can not register more than 256 operations");
+        }
+        self.ops.push(op);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Operation> {
+        self.ops.iter().find(|op| op.name == name).copied()
+    }
+
+    pub fn opcode(&self, op: Operation) -> Option<u8> {
+        self.ops.iter().position(|o| *o == op).map(|ix| ix as u8)
+    }
+
+    pub fn by_opcode(&self, opcode: u8) -> Option<Operation> {
+        self.ops.get(opcode as usize).copied()
+    }
+
+    /// Parse `s`, a slice of `line`, as the name of an operation
+    pub fn parse_operation(&self, line: &str, s: &str) -> Result<Operation, ParseError> {
+        self.get(s.trim()).ok_or_else(|| {
+            let names = self
+                .ops
+                .iter()
+                .map(|op| format!("{:?}", op.name))
+                .collect::<Vec<_>>();
+            ParseError::at(line, s, format!("one of {}", names.join(", ")))
+        })
+    }
+
+    /// Parse an instruction such as `acc +3`
+    pub fn parse_instruction(&self, line: &str) -> Result<Instruction, ParseError> {
+        let (op_str, arg_str) = split_once(line, line.trim(), " ")?;
+        let op = self.parse_operation(line, op_str)?;
+        let arg = parse_at(line, arg_str, "an integer argument")?;
+        Ok(Instruction { op, arg })
+    }
+}
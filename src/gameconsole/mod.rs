@@ -1,35 +1,23 @@
-use anyhow::{anyhow as ah, Context, Result};
-use std::{fmt, str::FromStr};
+use crate::parsing::{parse_lines, ParseError};
+use anyhow::Result;
+use std::{collections::HashSet, fmt, rc::Rc, str::FromStr};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum Operation {
-    Nop,
-    Acc,
-    Jmp,
-}
-impl fmt::Debug for Operation {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Operation::Nop => write!(f, "nop"),
-            Operation::Acc => write!(f, "acc"),
-            Operation::Jmp => write!(f, "jmp"),
-        }
-    }
-}
+pub mod analysis;
+pub mod asm;
+mod bytecode;
+pub use bytecode::is_bytecode;
+pub mod debugger;
+pub mod generate;
+mod history;
+pub mod optimize;
+pub use history::{History, Snapshot};
+mod instruction_set;
+pub use instruction_set::{Flow, InstructionSet, Operation};
+mod trace;
+pub use trace::{Trace, TraceEntry};
 
-impl FromStr for Operation {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.trim();
-        match s {
-            "nop" => Ok(Operation::Nop),
-            "acc" => Ok(Operation::Acc),
-            "jmp" => Ok(Operation::Jmp),
-            _ => Err(ah!("unrecognized operation: {:?}", s)),
-        }
-    }
-}
+/// General purpose registers, for operations beyond the accumulator
+pub const REGISTERS: usize = 4;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Instruction {
@@ -39,97 +27,261 @@ pub struct Instruction {
 
 impl fmt::Debug for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let (sign, abs) = if self.arg < 0 {
-            ('-', -1 * self.arg)
-        } else {
-            ('+', self.arg)
-        };
+        let sign = if self.arg < 0 { '-' } else { '+' };
+        let abs = self.arg.unsigned_abs();
         write!(f, "{:?} {}{}", self.op, sign, abs)
     }
 }
 
 impl FromStr for Instruction {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.trim();
-        let mut split = s.splitn(2, " ").into_iter();
-        let op_str = split.next().ok_or_else(|| ah!("instruction was empty"))?;
-        let arg_str = split
-            .next()
-            .ok_or_else(|| ah!("instruction was not space separated"))?;
-        let op = Operation::from_str(op_str)?;
-        let arg = arg_str
-            .parse::<i64>()
-            .with_context(|| format!("{:?} was not an integer", arg_str))?;
-        Ok(Instruction { op, arg })
+    type Err = ParseError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        InstructionSet::default().parse_instruction(line)
     }
 }
 
+/// A program, shared between clones until one of them changes it
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Code {
-    pub data: Vec<Instruction>,
+    pub data: Rc<Vec<Instruction>>,
 }
 
 impl Code {
+    pub fn new(data: Vec<Instruction>) -> Code {
+        Code {
+            data: Rc::new(data),
+        }
+    }
     pub fn len(&self) -> usize {
         self.data.len()
     }
+    /// Replace an instruction, copying the program first if it is shared
+    pub fn set(&mut self, ix: usize, instruction: Instruction) {
+        Rc::make_mut(&mut self.data)[ix] = instruction;
+    }
     pub fn parse(s: &str) -> Result<Code> {
-        Ok(Code {
-            data: s
-                .lines()
-                .map(|l| Instruction::from_str(l))
-                .collect::<Result<Vec<_>>>()?,
-        })
+        Code::parse_with(&InstructionSet::default(), s)
+    }
+    pub fn parse_with(set: &InstructionSet, s: &str) -> Result<Code> {
+        Ok(Code::new(parse_lines(s, |l| set.parse_instruction(l))?))
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// The instruction ran and `ix` points at another instruction
+    Continued,
+    /// `ix` is just past the last instruction, the program has finished
+    Terminated,
+    /// The jump at `ix` would go to `target`, outside of the program. A target which does not
+    /// fit in an `i64` is given as `i64::MIN` or `i64::MAX`.
+    JumpOutOfBounds { ix: usize, target: i64 },
+    /// `ix` is past the end of the program, so there is nothing to run
+    IndexOutOfBounds { ix: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunResult {
+    /// The stop condition was met before running the instruction at `ix`
+    Stopped {
+        ix: usize,
+    },
+    Terminated,
+    /// The instruction at `ix` was about to run for a second time, so the program never ends
+    Loop {
+        ix: usize,
+    },
+    JumpOutOfBounds {
+        ix: usize,
+        target: i64,
+    },
+    IndexOutOfBounds {
+        ix: usize,
+    },
+}
+
+/// The instructions run into a loop, then round it, ending where the loop starts again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopPath {
+    pub lead_in: Vec<usize>,
+    pub cycle: Vec<usize>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Machine {
     pub acc: i64,
+    pub regs: [i64; REGISTERS],
     pub ix: usize,
     pub code: Code,
+    /// Executed instructions are recorded here when it is set
+    pub trace: Option<Trace>,
+    /// The state before each instruction is recorded here when it is set, for `back`
+    pub history: Option<History>,
 }
 
 impl Machine {
     pub fn new(code: Code) -> Machine {
         Machine {
             acc: 0,
+            regs: [0; REGISTERS],
             ix: 0,
             code,
+            trace: None,
+            history: None,
         }
     }
 
-    pub fn step(&mut self) {
-        let instr = self.code.data[self.ix];
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            acc: self.acc,
+            regs: self.regs,
+            ix: self.ix,
+            code: self.code.clone(),
+        }
+    }
+
+    /// Go back to a snapshot, which also forgets the history
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.load(snapshot.clone());
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    fn load(&mut self, snapshot: Snapshot) {
+        self.acc = snapshot.acc;
+        self.regs = snapshot.regs;
+        self.ix = snapshot.ix;
+        self.code = snapshot.code;
+    }
+
+    /// Undo up to `n` instructions using the history, returning how many were undone.
+    ///
+    /// The trace is left alone, it still has the undone instructions.
+    pub fn back(&mut self, n: usize) -> usize {
+        let mut undone = 0;
+        while undone < n {
+            match self.history.as_mut().and_then(History::pop) {
+                Some(snapshot) => self.load(snapshot),
+                None => break,
+            }
+            undone += 1;
+        }
+        undone
+    }
+
+    /// How the machine got into the loop it is in, if the history goes back far enough to show
+    /// the cycle.
+    ///
+    /// After `run` finds a loop this is the instructions run before the loop, as far back as
+    /// the history goes, then the cycle the program is stuck in. With conditional operations
+    /// the accumulator and registers have to match too.
+    pub fn loop_path(&self) -> Option<LoopPath> {
+        let history = self.history.as_ref()?;
+        let conditional = self.code.data.iter().any(|i| i.op.is_conditional());
+        let same = |s: &Snapshot| {
+            s.ix == self.ix && (!conditional || (s.acc, s.regs) == (self.acc, self.regs))
+        };
+        let back = history.snapshots().rev().position(same)?;
+        let start = history.len().checked_sub(back + 1)?;
+        let mut ixs = history.snapshots().map(|s| s.ix).collect::<Vec<_>>();
+        let mut cycle = ixs.split_off(start);
+        cycle.push(self.ix);
+        Some(LoopPath {
+            lead_in: ixs,
+            cycle,
+        })
+    }
+
+    /// Run a single instruction.
+    ///
+    /// A jump out of bounds leaves the machine as it was, and is not added to the history or
+    /// the trace.
+    pub fn step(&mut self) -> StepResult {
+        let instr = match self.code.data.get(self.ix) {
+            Some(instr) => *instr,
+            None if self.ix == self.code.len() => return StepResult::Terminated,
+            None => return StepResult::IndexOutOfBounds { ix: self.ix },
+        };
 
         log::trace!("acc: {} ix: {} => {:?}", self.acc, self.ix, instr);
 
-        match instr.op {
-            Operation::Nop => {
-                self.ix += 1;
+        let snapshot = self.history.as_ref().map(|_| self.snapshot());
+        let (acc_before, regs_before) = (self.acc, self.regs);
+        let offset = match instr.op.exec(self, instr.arg) {
+            Flow::Next => 1,
+            Flow::Jump(offset) => offset,
+        };
+        let next = match (self.ix as i64).checked_add(offset) {
+            Some(target) if target >= 0 && target <= self.code.len() as i64 => target as usize,
+            target => {
+                self.acc = acc_before;
+                self.regs = regs_before;
+                return StepResult::JumpOutOfBounds {
+                    ix: self.ix,
+                    target: target.unwrap_or(if offset < 0 { i64::MIN } else { i64::MAX }),
+                };
+            }
+        };
+
+        if let (Some(history), Some(snapshot)) = (&mut self.history, snapshot) {
+            history.push(snapshot);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.record(TraceEntry {
+                ix: self.ix,
+                instruction: instr,
+                acc_before,
+                acc_after: self.acc,
+            });
+        }
+        self.ix = next;
+
+        if self.ix == self.code.len() {
+            StepResult::Terminated
+        } else {
+            StepResult::Continued
+        }
+    }
+
+    /// Step until `stop` is true for the machine, the program ends, or it is stuck in a loop.
+    ///
+    /// Without conditional operations, an instruction running a second time means the
+    /// program never ends. With them, the whole state of the machine has to repeat.
+    pub fn run_until<F: FnMut(&Machine) -> bool>(&mut self, mut stop: F) -> RunResult {
+        let conditional = self.code.data.iter().any(|i| i.op.is_conditional());
+        let mut seen = vec![false; self.code.len()];
+        let mut seen_states = HashSet::new();
+        loop {
+            if stop(self) {
+                return RunResult::Stopped { ix: self.ix };
             }
-            Operation::Acc => {
-                self.acc += instr.arg;
-                self.ix += 1;
+            let repeated = if conditional {
+                !seen_states.insert((self.ix, self.acc, self.regs))
+            } else {
+                seen.get_mut(self.ix)
+                    .map(|s| std::mem::replace(s, true))
+                    .unwrap_or(false)
+            };
+            if repeated {
+                return RunResult::Loop { ix: self.ix };
             }
-            Operation::Jmp => {
-                if instr.arg < 0 {
-                    let arg = (-1 * instr.arg) as usize;
-                    assert!(
-                        arg < self.ix,
-                        "attempt to jump out of bounds {} - {}",
-                        self.ix,
-                        arg
-                    );
-                    self.ix -= arg
-                } else {
-                    self.ix += instr.arg as usize;
+            match self.step() {
+                StepResult::Continued => {}
+                StepResult::Terminated => return RunResult::Terminated,
+                StepResult::JumpOutOfBounds { ix, target } => {
+                    return RunResult::JumpOutOfBounds { ix, target }
                 }
+                StepResult::IndexOutOfBounds { ix } => return RunResult::IndexOutOfBounds { ix },
             }
         }
     }
+
+    /// Run until the program ends or loops
+    pub fn run(&mut self) -> RunResult {
+        self.run_until(|_| false)
+    }
 }
 
 #[cfg(test)]
@@ -141,44 +293,200 @@ mod tests {
         assert_eq!(
             Instruction::from_str("nop +0").unwrap(),
             Instruction {
-                op: Operation::Nop,
+                op: Operation::NOP,
                 arg: 0
             }
         );
         assert_eq!(
             Instruction::from_str("nop -0").unwrap(),
             Instruction {
-                op: Operation::Nop,
+                op: Operation::NOP,
                 arg: 0
             }
         );
         assert_eq!(
             Instruction::from_str("acc +1").unwrap(),
             Instruction {
-                op: Operation::Acc,
+                op: Operation::ACC,
                 arg: 1
             }
         );
         assert_eq!(
             Instruction::from_str("acc +19").unwrap(),
             Instruction {
-                op: Operation::Acc,
+                op: Operation::ACC,
                 arg: 19
             }
         );
         assert_eq!(
             Instruction::from_str("acc -4").unwrap(),
             Instruction {
-                op: Operation::Acc,
+                op: Operation::ACC,
                 arg: -4
             }
         );
         assert_eq!(
             Instruction::from_str("jmp +1").unwrap(),
             Instruction {
-                op: Operation::Jmp,
+                op: Operation::JMP,
                 arg: 1
             }
         );
     }
+
+    #[test]
+    fn parse_malformed() {
+        let e = Code::parse("nop +0\nacc 1x\n").unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!((e.line(), e.columns()), (2, 5..7));
+        assert_eq!(Instruction::from_str("mov +1").unwrap_err().columns(), 1..4);
+        assert_eq!(Instruction::from_str("jmp").unwrap_err().columns(), 4..4);
+    }
+
+    fn machine(src: &str) -> Machine {
+        Machine::new(Code::parse(src).unwrap())
+    }
+
+    #[test]
+    fn step_results() {
+        let mut vm = machine("acc +2\njmp -1");
+        assert_eq!(vm.step(), StepResult::Continued);
+        assert_eq!(vm.step(), StepResult::Continued);
+        assert_eq!((vm.ix, vm.acc), (0, 2));
+
+        let mut vm = machine("nop +0\njmp -2");
+        vm.step();
+        assert_eq!(vm.step(), StepResult::JumpOutOfBounds { ix: 1, target: -1 });
+        assert_eq!(vm.ix, 1);
+
+        let mut vm = machine("jmp +3\nnop +0");
+        assert_eq!(vm.step(), StepResult::JumpOutOfBounds { ix: 0, target: 3 });
+
+        let mut vm = machine("nop +0\njmp +9223372036854775807");
+        vm.step();
+        assert_eq!(
+            vm.step(),
+            StepResult::JumpOutOfBounds {
+                ix: 1,
+                target: i64::MAX
+            }
+        );
+        let mut vm = machine("nop +0\njmp -9223372036854775808");
+        vm.step();
+        assert_eq!(
+            vm.step(),
+            StepResult::JumpOutOfBounds {
+                ix: 1,
+                target: i64::MIN + 1
+            }
+        );
+        let mut vm = machine("nop +0\nnop +0\njmp +9223372036854775807");
+        assert_eq!(
+            vm.run(),
+            RunResult::JumpOutOfBounds {
+                ix: 2,
+                target: i64::MAX
+            }
+        );
+
+        let mut vm = machine("acc +1\njmp +1");
+        assert_eq!(vm.step(), StepResult::Continued);
+        assert_eq!(vm.step(), StepResult::Terminated);
+        assert_eq!(vm.step(), StepResult::Terminated);
+        vm.ix = 5;
+        assert_eq!(vm.step(), StepResult::IndexOutOfBounds { ix: 5 });
+    }
+
+    #[test]
+    fn run_results() {
+        let mut vm = machine("acc +1\nacc +2\njmp -2");
+        assert_eq!(vm.run(), RunResult::Loop { ix: 0 });
+        assert_eq!(vm.acc, 3);
+
+        let mut vm = machine("acc +1\nacc +2\nnop -2");
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, 3);
+
+        let mut vm = machine("acc +1\nacc +2\njmp -5");
+        assert_eq!(vm.run(), RunResult::JumpOutOfBounds { ix: 2, target: -3 });
+
+        let mut vm = machine("acc +1\nacc +2\nnop -2");
+        assert_eq!(vm.run_until(|m| m.acc > 0), RunResult::Stopped { ix: 1 });
+    }
+
+    #[test]
+    fn accumulator_wraps() {
+        let mut vm = machine("acc +9223372036854775807\nacc +9223372036854775807\nacc +3");
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, 1);
+        let mut vm = machine("acc -9223372036854775808\nacc -1");
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, i64::MAX);
+    }
+
+    #[test]
+    fn extended_instruction_set() {
+        const MUL: Operation = Operation::new("mul", |m, arg| {
+            m.acc = m.acc.wrapping_mul(arg);
+            Flow::Next
+        });
+        // jump if register 0 is not zero, counting it down
+        const LOOP: Operation = Operation::conditional("loop", |m, arg| {
+            if m.regs[0] == 0 {
+                return Flow::Next;
+            }
+            m.regs[0] = m.regs[0].wrapping_sub(1);
+            Flow::Jump(arg)
+        });
+        let mut set = InstructionSet::default();
+        set.register(MUL).unwrap();
+        set.register(LOOP).unwrap();
+        assert!(set.register(MUL).is_err());
+
+        let code = Code::parse_with(&set, "acc +1\nmul +2\nloop -1\nnop +0").unwrap();
+        assert_eq!(format!("{:?}", code.data[2]), "loop -1");
+        let mut vm = Machine::new(code);
+        vm.regs[0] = 3;
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, 16);
+
+        let code = Code::parse_with(&set, "loop +0").unwrap();
+        let mut vm = Machine::new(code);
+        vm.regs[0] = 2;
+        assert_eq!(vm.run(), RunResult::Terminated);
+        let code = Code::parse_with(&set, "nop +0\njmp -1\nloop +0").unwrap();
+        assert_eq!(Machine::new(code).run(), RunResult::Loop { ix: 0 });
+
+        let code = Code::parse_with(&set, "acc +1\nmul +3\nmul -2").unwrap();
+        let mut vm = Machine::new(code);
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, -6);
+
+        let code = Code::parse_with(&set, "acc +9223372036854775807\nmul +2").unwrap();
+        let mut vm = Machine::new(code);
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, -2);
+
+        let e = Code::parse("acc +1\nmul +3").unwrap_err();
+        let e = e.downcast_ref::<ParseError>().unwrap();
+        assert_eq!(e.expected(), "one of \"nop\", \"acc\", \"jmp\"");
+    }
+
+    #[test]
+    fn extended_set() {
+        let set = InstructionSet::extended();
+        let code = Code::parse_with(&set, "acc +2\nmul +3\nacc -1\njnz -1").unwrap();
+        let mut vm = Machine::new(code.clone());
+        assert_eq!(vm.run(), RunResult::Terminated);
+        assert_eq!(vm.acc, 0);
+        assert_eq!(
+            Code::from_bytes_with(&set, &code.to_bytes_with(&set).unwrap()).unwrap(),
+            code
+        );
+        assert!(code.to_bytes().is_err());
+
+        let code = Code::parse_with(&set, "acc +1\njnz +0").unwrap();
+        assert_eq!(Machine::new(code).run(), RunResult::Loop { ix: 1 });
+        assert!(Code::parse("jnz +0").is_err());
+    }
 }
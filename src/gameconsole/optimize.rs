@@ -0,0 +1,434 @@
+use super::{Code, History, Instruction, LoopPath, Machine, Operation, RunResult, Trace};
+use anyhow::Result;
+use std::collections::HashSet;
+
+/// How many of the last instructions run `compare` keeps in its trace
+const TRACE_LIMIT: usize = 20;
+
+/// Optimized code, and where each of its instructions came from in the original
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optimized {
+    pub code: Code,
+    /// The original address of each instruction, with one more entry for the end
+    addresses: Vec<usize>,
+}
+
+/// How a program run ended, and the accumulator at that point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    pub result: RunResult,
+    pub acc: i64,
+}
+
+/// The outcome of running the original and the optimized code, with optimized addresses
+/// mapped back to the original
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    pub original: Outcome,
+    pub optimized: Outcome,
+    /// The loop the original code got stuck in, if it did
+    pub original_path: Option<LoopPath>,
+    pub optimized_path: Option<LoopPath>,
+    /// The last instructions the optimized code ran. Folded `acc` instructions show their sum.
+    pub optimized_trace: Trace,
+}
+
+impl Comparison {
+    /// Whether both ended the same way.
+    ///
+    /// A loop is found when its first instruction runs again, which may have been removed
+    /// from the optimized code, so the loops may be found at different instructions. They
+    /// match when the accumulators are equal and every instruction in the optimized cycle,
+    /// including the one which was about to run again, is in the original cycle.
+    pub fn matches(&self) -> bool {
+        match (self.original.result, self.optimized.result) {
+            (RunResult::Loop { .. }, RunResult::Loop { .. }) => {
+                let (original, optimized) = match (&self.original_path, &self.optimized_path) {
+                    (Some(original), Some(optimized)) => (original, optimized),
+                    _ => return false,
+                };
+                let cycle = original.cycle.iter().collect::<HashSet<_>>();
+                self.original.acc == self.optimized.acc
+                    && !optimized.cycle.is_empty()
+                    && optimized.cycle.iter().all(|ix| cycle.contains(ix))
+            }
+            _ => self.original == self.optimized,
+        }
+    }
+}
+
+fn removable(instr: Instruction) -> bool {
+    instr.op == Operation::NOP || (instr.op == Operation::JMP && instr.arg == 1)
+}
+
+/// Where running from `target` first does something, skipping removable instructions and
+/// following jumps. Jumps out of the program and jumps which only go round in a cycle are
+/// kept, so they are returned. So are jumps whose target overflows, or is so far out that
+/// the distance to it would overflow.
+fn resolve(code: &Code, mut target: i64) -> i64 {
+    let len = code.len() as i64;
+    let mut seen = HashSet::new();
+    while target >= 0 && target < len {
+        let instr = code.data[target as usize];
+        let next = if removable(instr) {
+            target + 1
+        } else if instr.op == Operation::JMP {
+            match target.checked_add(instr.arg) {
+                Some(next) if next >= i64::MIN + len => next,
+                _ => break,
+            }
+        } else {
+            break;
+        };
+        let jump_out = next < 0 || next > len;
+        if jump_out && !removable(instr) {
+            break;
+        }
+        if !seen.insert(target) && !removable(instr) {
+            break;
+        }
+        target = next;
+    }
+    target
+}
+
+/// Fold consecutive `acc` instructions, remove `nop` and `jmp +1`, and make jumps go straight
+/// to the end of a chain of jumps.
+///
+/// This is a single pass, so a jump may end up as `jmp +1` again. A jump whose target
+/// overflows becomes `jmp +9223372036854775807`, which still goes out of bounds. Only `nop`,
+/// `acc` and `jmp` are understood, other operations are an error.
+pub fn optimize(code: &Code) -> Result<Optimized> {
+    let len = code.len();
+    for (ix, instr) in code.data.iter().enumerate() {
+        if ![Operation::NOP, Operation::ACC, Operation::JMP].contains(&instr.op) {
+            anyhow::bail!("can not optimize operation {:?} at {}", instr.op, ix);
+        }
+    }
+
+    // where each kept jump goes, before any instruction moves, unless its target overflows
+    let targets = code
+        .data
+        .iter()
+        .enumerate()
+        .map(|(ix, instr)| {
+            if instr.op == Operation::JMP && !removable(*instr) {
+                (ix as i64)
+                    .checked_add(instr.arg)
+                    .map(|target| resolve(code, target))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    let is_target = targets.iter().flatten().copied().collect::<HashSet<_>>();
+
+    // `group[ix]` is the new address of a kept instruction, or the one a folded `acc` is in
+    let mut data: Vec<Instruction> = Vec::new();
+    let mut addresses = Vec::new();
+    let mut group = vec![None; len];
+    for (ix, instr) in code.data.iter().enumerate() {
+        if removable(*instr) {
+            continue;
+        }
+        let fold_into = match data.last_mut() {
+            Some(last)
+                if instr.op == Operation::ACC
+                    && last.op == Operation::ACC
+                    && !is_target.contains(&(ix as i64)) =>
+            {
+                last.arg.checked_add(instr.arg).map(|arg| (last, arg))
+            }
+            _ => None,
+        };
+        match fold_into {
+            Some((last, arg)) => last.arg = arg,
+            None => {
+                addresses.push(ix);
+                data.push(*instr);
+            }
+        }
+        group[ix] = Some(data.len() - 1);
+    }
+    addresses.push(len);
+
+    // where running from each original address goes in the new code
+    let mut new_ix = vec![data.len(); len + 1];
+    for ix in (0..len).rev() {
+        new_ix[ix] = group[ix].unwrap_or(new_ix[ix + 1]);
+    }
+    let new_len = data.len() as i64;
+    for (ix, target) in targets.into_iter().enumerate() {
+        let instr = code.data[ix];
+        if instr.op != Operation::JMP || removable(instr) {
+            continue;
+        }
+        let at = new_ix[ix];
+        let target = match target {
+            Some(target) => target,
+            None => {
+                data[at].arg = i64::MAX;
+                continue;
+            }
+        };
+        let new_target = if target < 0 {
+            target
+        } else if target > len as i64 {
+            target - (len as i64 - new_len)
+        } else {
+            new_ix[target as usize] as i64
+        };
+        data[at].arg = new_target - at as i64;
+    }
+
+    Ok(Optimized {
+        code: Code::new(data),
+        addresses,
+    })
+}
+
+impl Optimized {
+    /// The original address for an address in the optimized code
+    pub fn original(&self, ix: usize) -> usize {
+        match self.addresses.get(ix) {
+            Some(original) => *original,
+            // past the end, keep the distance from the end
+            None => self.original_len() + ix - self.code.len(),
+        }
+    }
+
+    fn original_len(&self) -> usize {
+        *self
+            .addresses
+            .last()
+            .expect("there is always an end address")
+    }
+
+    fn original_target(&self, target: i64) -> i64 {
+        if target < 0 {
+            target
+        } else if target <= self.code.len() as i64 {
+            self.original(target as usize) as i64
+        } else {
+            // past the end, keep the distance from the end
+            target.saturating_add((self.original_len() - self.code.len()) as i64)
+        }
+    }
+
+    /// A run result with addresses in the original code
+    pub fn original_result(&self, result: RunResult) -> RunResult {
+        match result {
+            RunResult::Stopped { ix } => RunResult::Stopped {
+                ix: self.original(ix),
+            },
+            RunResult::Terminated => RunResult::Terminated,
+            RunResult::Loop { ix } => RunResult::Loop {
+                ix: self.original(ix),
+            },
+            RunResult::JumpOutOfBounds { ix, target } => RunResult::JumpOutOfBounds {
+                ix: self.original(ix),
+                target: self.original_target(target),
+            },
+            RunResult::IndexOutOfBounds { ix } => RunResult::IndexOutOfBounds {
+                ix: self.original(ix),
+            },
+        }
+    }
+
+    /// A trace of the optimized code, with addresses in the original code
+    pub fn original_trace(&self, trace: &Trace) -> Trace {
+        trace.map_addresses(|ix| self.original(ix))
+    }
+
+    /// A path such as `Machine::loop_path`, with addresses in the original code
+    pub fn original_path(&self, path: &[usize]) -> Vec<usize> {
+        path.iter().map(|ix| self.original(*ix)).collect()
+    }
+}
+
+/// Run `code` keeping the whole history, so a loop can be found
+fn run(code: Code) -> (Outcome, Machine) {
+    let mut vm = Machine::new(code);
+    vm.history = Some(History::default());
+    vm.trace = Some(Trace::with_limit(TRACE_LIMIT));
+    let result = vm.run();
+    let outcome = Outcome {
+        result,
+        acc: vm.acc,
+    };
+    (outcome, vm)
+}
+
+/// Run both the original and the optimized code
+pub fn compare(code: &Code) -> Result<Comparison> {
+    let optimized = optimize(code)?;
+    let (original, original_vm) = run(code.clone());
+    let (mut outcome, vm) = run(optimized.code.clone());
+    outcome.result = optimized.original_result(outcome.result);
+    let loop_path = |outcome: &Outcome, vm: &Machine| match outcome.result {
+        RunResult::Loop { .. } => vm.loop_path(),
+        _ => None,
+    };
+    Ok(Comparison {
+        original_path: loop_path(&original, &original_vm),
+        optimized_path: loop_path(&outcome, &vm).map(|path| LoopPath {
+            lead_in: optimized.original_path(&path.lead_in),
+            cycle: optimized.original_path(&path.cycle),
+        }),
+        optimized_trace: optimized.original_trace(vm.trace.as_ref().expect("trace is set")),
+        original,
+        optimized: outcome,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{asm, generate::Generator, History};
+    use super::*;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const EX: &str = include_str!("../../input/day8_ex");
+
+    fn optimized(src: &str) -> Optimized {
+        optimize(&Code::parse(src).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn folds_and_removes() {
+        let o = optimized("acc +1\nnop +5\nacc +2\njmp +1\nacc -4\njmp -5");
+        assert_eq!(asm::to_plain(&o.code), "acc -1\njmp -1\n");
+        assert_eq!((o.original(0), o.original(1), o.original(2)), (0, 5, 6));
+    }
+
+    #[test]
+    fn jump_targets_are_not_folded() {
+        let o = optimized("acc +1\nacc +2\nacc +3\njmp -2");
+        assert_eq!(asm::to_plain(&o.code), "acc +1\nacc +5\njmp -1\n");
+        assert_eq!(o.original(1), 1);
+        let o = optimized("acc +1\nnop +0\nacc +2\njmp -2");
+        assert_eq!(asm::to_plain(&o.code), "acc +1\nacc +2\njmp -1\n");
+    }
+
+    #[test]
+    fn threads_jumps() {
+        let o = optimized("jmp +2\nacc +1\njmp +2\nacc +2\nnop +0\nacc +3");
+        assert_eq!(
+            asm::to_plain(&o.code),
+            "jmp +4\nacc +1\njmp +2\nacc +2\nacc +3\n"
+        );
+        // cycles of jumps and jumps out of the program are kept
+        let o = optimized("jmp +1\nnop +0\njmp -1\njmp +3\njmp -1");
+        assert_eq!(asm::to_plain(&o.code), "jmp +0\njmp +3\njmp -1\n");
+        assert_eq!(
+            o.original_result(Machine::new(o.code.clone()).run()),
+            RunResult::Loop { ix: 2 }
+        );
+        let o = optimized("jmp +1\njmp +1\njmp +3\nacc +1\njmp -4");
+        assert_eq!(asm::to_plain(&o.code), "jmp +3\nacc +1\njmp +1\n");
+        let c = compare(&Code::parse("nop +0\njmp +1\njmp +4\nacc +1\njmp -4").unwrap());
+        assert_eq!(
+            c.unwrap().optimized.result,
+            RunResult::JumpOutOfBounds { ix: 2, target: 6 }
+        );
+    }
+
+    #[test]
+    fn example_maps_back() {
+        let code = Code::parse(EX).unwrap();
+        let o = optimize(&code).unwrap();
+        assert_eq!(o.code.len(), 8);
+
+        let c = compare(&code).unwrap();
+        assert!(c.matches());
+        assert_eq!(c.original, c.optimized);
+        assert_eq!(c.optimized.result, RunResult::Loop { ix: 1 });
+
+        let mut vm = Machine::new(o.code.clone());
+        vm.trace = Some(Trace::default());
+        vm.history = Some(History::default());
+        vm.run();
+        let trace = o.original_trace(vm.trace.as_ref().unwrap());
+        assert_eq!(
+            trace.hits().keys().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 6, 7]
+        );
+        assert_eq!(
+            o.original_path(&vm.loop_path().unwrap().cycle),
+            vec![1, 2, 6, 7, 3, 4, 1]
+        );
+
+        assert_eq!(c.optimized_trace.hits(), trace.hits());
+        let path = c.optimized_path.as_ref().unwrap();
+        assert!(path.lead_in.is_empty());
+        assert_eq!(path.cycle, vec![1, 2, 6, 7, 3, 4, 1]);
+        assert_eq!(c.original_path.as_ref().unwrap().cycle, path.cycle);
+    }
+
+    #[test]
+    fn loops_have_to_be_the_same_cycle() {
+        let mut c = compare(&Code::parse("acc +1\nnop +0\njmp -2\njmp -1").unwrap()).unwrap();
+        assert!(c.matches(), "{:?}", c);
+        // the optimized code starts at the `jmp -2` and never runs the `nop +0`
+        assert_eq!(c.original_path.as_ref().unwrap().cycle, vec![0, 1, 2, 0]);
+        assert_eq!(c.optimized_path.as_ref().unwrap().cycle, vec![0, 2, 0]);
+
+        // same result and accumulator, but stuck somewhere else
+        c.optimized_path = Some(LoopPath {
+            lead_in: vec![0, 1, 2],
+            cycle: vec![3, 3],
+        });
+        assert!(!c.matches());
+        c.optimized_path = None;
+        assert!(!c.matches());
+    }
+
+    #[test]
+    fn overflowing_jumps() {
+        let src = "acc +1\nnop +0\nacc +2\njmp +9223372036854775807\njmp -9223372036854775808";
+        let o = optimized(src);
+        assert_eq!(
+            asm::to_plain(&o.code),
+            "acc +3\njmp +9223372036854775807\njmp -9223372036854775806\n"
+        );
+        let c = compare(&Code::parse(src).unwrap()).unwrap();
+        assert_eq!(
+            c.optimized.result,
+            RunResult::JumpOutOfBounds {
+                ix: 3,
+                target: i64::MAX
+            }
+        );
+        assert!(c.matches(), "{:?}", c);
+
+        let src = "jmp +2\nacc +1\njmp -9223372036854775808\nacc +1";
+        let c = compare(&Code::parse(src).unwrap()).unwrap();
+        assert!(c.matches(), "{:?}", c);
+    }
+
+    #[test]
+    fn other_operations() {
+        let mut set = super::super::InstructionSet::default();
+        set.register(Operation::new("hlt", |_, _| {
+            super::super::Flow::Jump(1_000)
+        }))
+        .unwrap();
+        let code = Code::parse_with(&set, "nop +0\nhlt +0").unwrap();
+        assert!(optimize(&code).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn same_outcome(seed: u64, len in 0usize..200, loop_density in 0.0..1.0) {
+            let gen = Generator {
+                len,
+                loop_density,
+                nop: 0.3,
+                ..Generator::default()
+            };
+            let code = gen.code(&mut StdRng::seed_from_u64(seed));
+            let c = compare(&code).unwrap();
+            prop_assert!(c.matches(), "{:?}", c);
+        }
+    }
+}
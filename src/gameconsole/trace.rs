@@ -0,0 +1,215 @@
+use super::Instruction;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+};
+
+/// A single executed instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub ix: usize,
+    pub instruction: Instruction,
+    pub acc_before: i64,
+    pub acc_after: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hits {
+    pub instruction: Instruction,
+    pub count: u64,
+}
+
+/// Executed instructions, and how many times each instruction was run
+///
+/// Only the most recent `limit` entries are kept, but hits are counted for the whole run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Trace {
+    entries: VecDeque<TraceEntry>,
+    limit: Option<usize>,
+    hits: BTreeMap<usize, Hits>,
+}
+
+#[derive(Serialize)]
+struct JsonEntry {
+    ix: usize,
+    instruction: String,
+    acc_before: i64,
+    acc_after: i64,
+}
+
+#[derive(Serialize)]
+struct JsonHits {
+    ix: usize,
+    instruction: String,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct JsonTrace {
+    entries: Vec<JsonEntry>,
+    hits: Vec<JsonHits>,
+}
+
+impl Trace {
+    /// Keep only the last `limit` entries
+    pub fn with_limit(limit: usize) -> Trace {
+        Trace {
+            limit: Some(limit),
+            ..Trace::default()
+        }
+    }
+
+    pub fn record(&mut self, entry: TraceEntry) {
+        self.hits
+            .entry(entry.ix)
+            .or_insert(Hits {
+                instruction: entry.instruction,
+                count: 0,
+            })
+            .count += 1;
+        match self.limit {
+            Some(0) => return,
+            Some(limit) if self.entries.len() == limit => {
+                self.entries.pop_front();
+            }
+            _ => {}
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    /// Hit counts by instruction index, for every instruction which was run
+    pub fn hits(&self) -> &BTreeMap<usize, Hits> {
+        &self.hits
+    }
+
+    /// The same trace with each instruction index changed by `f`
+    pub fn map_addresses<F: Fn(usize) -> usize>(&self, f: F) -> Trace {
+        Trace {
+            entries: self
+                .entries
+                .iter()
+                .map(|e| TraceEntry { ix: f(e.ix), ..*e })
+                .collect(),
+            limit: self.limit,
+            hits: self.hits.iter().map(|(ix, h)| (f(*ix), *h)).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let json = JsonTrace {
+            entries: self
+                .entries()
+                .map(|e| JsonEntry {
+                    ix: e.ix,
+                    instruction: format!("{:?}", e.instruction),
+                    acc_before: e.acc_before,
+                    acc_after: e.acc_after,
+                })
+                .collect(),
+            hits: self
+                .hits()
+                .iter()
+                .map(|(ix, h)| JsonHits {
+                    ix: *ix,
+                    instruction: format!("{:?}", h.instruction),
+                    count: h.count,
+                })
+                .collect(),
+        };
+        serde_json::to_string(&json).expect("traces are always serializable")
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6}  {:<12} {:>12} {:>12}",
+            "ix", "instruction", "acc before", "acc after"
+        )?;
+        for e in self.entries() {
+            writeln!(
+                f,
+                "{:>6}  {:<12} {:>12} {:>12}",
+                e.ix,
+                format!("{:?}", e.instruction),
+                e.acc_before,
+                e.acc_after
+            )?;
+        }
+        writeln!(f)?;
+        write!(f, "{:>6}  {:<12} {:>12}", "ix", "instruction", "hits")?;
+        for (ix, h) in self.hits() {
+            write!(
+                f,
+                "\n{:>6}  {:<12} {:>12}",
+                ix,
+                format!("{:?}", h.instruction),
+                h.count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Code, Machine, RunResult};
+    use super::*;
+
+    fn traced(src: &str, trace: Trace) -> (Machine, RunResult) {
+        let mut vm = Machine::new(Code::parse(src).unwrap());
+        vm.trace = Some(trace);
+        let r = vm.run();
+        (vm, r)
+    }
+
+    #[test]
+    fn records_loop() {
+        let (vm, r) = traced("nop +0\nacc +1\njmp -1", Trace::default());
+        assert_eq!(r, RunResult::Loop { ix: 1 });
+        let trace = vm.trace.unwrap();
+        let entries = trace
+            .entries()
+            .map(|e| (e.ix, e.acc_before, e.acc_after))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![(0, 0, 0), (1, 0, 1), (2, 1, 1)]);
+        let hits = trace.hits().values().map(|h| h.count).collect::<Vec<_>>();
+        assert_eq!(hits, vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn limit_keeps_latest() {
+        let (vm, _) = traced("acc +1\nacc +2\nacc +3", Trace::with_limit(2));
+        let trace = vm.trace.unwrap();
+        assert_eq!(
+            trace.entries().map(|e| e.ix).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(trace.hits().len(), 3);
+
+        let (vm, _) = traced("acc +1", Trace::with_limit(0));
+        let trace = vm.trace.unwrap();
+        assert_eq!(trace.entries().count(), 0);
+        assert_eq!(trace.hits()[&0].count, 1);
+    }
+
+    #[test]
+    fn dump() {
+        let (vm, _) = traced("acc -2\nnop +0", Trace::default());
+        let trace = vm.trace.unwrap();
+        assert_eq!(
+            trace.to_json(),
+            r#"{"entries":[{"ix":0,"instruction":"acc -2","acc_before":0,"acc_after":-2},{"ix":1,"instruction":"nop +0","acc_before":-2,"acc_after":-2}],"hits":[{"ix":0,"instruction":"acc -2","count":1},{"ix":1,"instruction":"nop +0","count":1}]}"#
+        );
+        let table = trace.to_string();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines[1], "     0  acc -2                  0           -2");
+        assert_eq!(lines[6], "     1  nop +0                  1");
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{anyhow as ah, Result};
 
 mod challenge;
+mod fetch;
 mod gameconsole;
+mod parsing;
 
 fn main() -> Result<()> {
     color_backtrace::install();
@@ -9,7 +11,21 @@ fn main() -> Result<()> {
     setup_logger(args.occurrences_of("verbosity"));
     log::trace!("Args: {:?}", args);
 
-    challenge::run(&args).map_err(|e| {
+    let result = match args.subcommand() {
+        ("all", Some(sub_m)) => challenge::run_all(sub_m),
+        ("bench", Some(sub_m)) => challenge::run_bench(sub_m),
+        ("fetch", Some(sub_m)) => fetch::run(sub_m),
+        ("new", Some(sub_m)) => challenge::run_new(sub_m),
+        ("watch", Some(sub_m)) => challenge::run_watch(sub_m),
+        ("expenses", Some(sub_m)) => challenge::run_expenses(sub_m),
+        ("passwords", Some(sub_m)) => challenge::run_passwords(sub_m),
+        ("toboggan", Some(sub_m)) => challenge::run_toboggan(sub_m),
+        ("asm", Some(sub_m)) => gameconsole::asm::run(sub_m),
+        ("debug", Some(sub_m)) => gameconsole::debugger::run(sub_m),
+        _ => challenge::run(&args),
+    };
+
+    result.map_err(|e| {
         log::error!("{}", e);
         e.chain()
             .skip(1)
@@ -55,6 +71,8 @@ fn get_args() -> clap::ArgMatches<'static> {
         .version(clap::crate_version!())
         .about(clap::crate_description!())
         .setting(clap::AppSettings::DeriveDisplayOrder)
+        .setting(clap::AppSettings::SubcommandsNegateReqs)
+        .setting(clap::AppSettings::ArgsNegateSubcommands)
         .arg(
             clap::Arg::with_name("verbosity")
                 .short("v")
@@ -62,6 +80,27 @@ fn get_args() -> clap::ArgMatches<'static> {
                 .global(true)
                 .help("Sets the level of verbosity"),
         )
+        .arg(
+            clap::Arg::with_name("format")
+                .long("format")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .help("how answers are printed, defaults to text"),
+        )
+        .arg(
+            clap::Arg::with_name("check")
+                .long("check")
+                .global(true)
+                .help("compare answers against the known answers file"),
+        )
+        .arg(
+            clap::Arg::with_name("answers")
+                .long("answers")
+                .global(true)
+                .takes_value(true)
+                .help("path of the known answers file, defaults to answers.toml"),
+        )
         .arg(
             clap::Arg::with_name("raw")
                 .short("r")
@@ -69,6 +108,302 @@ fn get_args() -> clap::ArgMatches<'static> {
         )
         .arg(clap::Arg::with_name("day").required(true))
         .arg(clap::Arg::with_name("part").required(true))
-        .arg(clap::Arg::with_name("input").required(true))
+        .arg(
+            clap::Arg::with_name("input")
+                .required(true)
+                .help("input file, or '-' to read stdin"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("all")
+                .about("run every registered day and print a table of results")
+                .arg(
+                    clap::Arg::with_name("input_dir")
+                        .long("input-dir")
+                        .default_value("input")
+                        .help("directory containing the dayN input files"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("bench")
+                .about("time parsing and solving of a single part over many runs")
+                .arg(
+                    clap::Arg::with_name("iterations")
+                        .short("n")
+                        .long("iterations")
+                        .default_value("10")
+                        .help("number of timed runs"),
+                )
+                .arg(
+                    clap::Arg::with_name("warmup")
+                        .long("warmup")
+                        .default_value("1")
+                        .help("number of untimed runs before measuring"),
+                )
+                .arg(
+                    clap::Arg::with_name("baseline")
+                        .long("baseline")
+                        .default_value(challenge::DEFAULT_BASELINE)
+                        .help("file of earlier medians to compare against, updated after the run"),
+                )
+                .arg(
+                    clap::Arg::with_name("no_save")
+                        .long("no-save")
+                        .help("compare against the baseline without replacing it"),
+                )
+                .arg(clap::Arg::with_name("day").required(true))
+                .arg(clap::Arg::with_name("part").required(true))
+                .arg(
+                    clap::Arg::with_name("input")
+                        .help("input file, or '-' to read stdin, defaults to input/dayN"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("fetch")
+                .about("download a day's input, unless it is already in the input directory")
+                .arg(
+                    clap::Arg::with_name("input_dir")
+                        .long("input-dir")
+                        .default_value("input")
+                        .help("directory the dayN input file is written to"),
+                )
+                .arg(
+                    clap::Arg::with_name("base_url")
+                        .long("base-url")
+                        .env("AOC_BASE_URL")
+                        .default_value(fetch::DEFAULT_BASE_URL)
+                        .help("server to download inputs from"),
+                )
+                .arg(
+                    clap::Arg::with_name("session")
+                        .long("session")
+                        .env("AOC_SESSION")
+                        .hide_env_values(true)
+                        .help("session cookie used to authenticate"),
+                )
+                .arg(
+                    clap::Arg::with_name("session_file")
+                        .long("session-file")
+                        .takes_value(true)
+                        .help("file holding the session cookie, defaults to .aoc-session"),
+                )
+                .arg(clap::Arg::with_name("day").required(true)),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("new")
+                .about("create and register a new day from the template")
+                .arg(
+                    clap::Arg::with_name("title")
+                        .long("title")
+                        .takes_value(true)
+                        .help("puzzle title, defaults to 'Day N'"),
+                )
+                .arg(
+                    clap::Arg::with_name("root")
+                        .long("root")
+                        .default_value(".")
+                        .help("root of this repository"),
+                )
+                .arg(clap::Arg::with_name("day").required(true)),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("watch")
+                .about("re-run a day whenever its input files change")
+                .arg(
+                    clap::Arg::with_name("part")
+                        .short("p")
+                        .long("part")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("part to run, defaults to every solved part"),
+                )
+                .arg(
+                    clap::Arg::with_name("example")
+                        .short("e")
+                        .long("example")
+                        .takes_value(true)
+                        .help("another input file to watch, such as input/dayN_ex"),
+                )
+                .arg(
+                    clap::Arg::with_name("debounce")
+                        .long("debounce")
+                        .default_value("200")
+                        .help("milliseconds to wait for writes to settle before re-running"),
+                )
+                .arg(clap::Arg::with_name("day").required(true))
+                .arg(clap::Arg::with_name("input").help("input file, defaults to input/dayN")),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("expenses")
+                .about("find day 1 expenses which add up to a target")
+                .arg(
+                    clap::Arg::with_name("k")
+                        .long("k")
+                        .default_value("2")
+                        .help("how many expenses to add up"),
+                )
+                .arg(
+                    clap::Arg::with_name("target")
+                        .long("target")
+                        .default_value("2020")
+                        .allow_hyphen_values(true)
+                        .help("the sum to look for"),
+                )
+                .arg(
+                    clap::Arg::with_name("all")
+                        .long("all")
+                        .help("print every set of expenses, not just the first"),
+                )
+                .arg(
+                    clap::Arg::with_name("input")
+                        .default_value("input/day1")
+                        .help("input file, or '-' to read stdin"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("passwords")
+                .about("check day 2 password entries against policies, listing every failure")
+                .arg(
+                    clap::Arg::with_name("policy")
+                        .short("p")
+                        .long("policy")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .default_value("count")
+                        .help(
+                            "policy every entry has to follow, one of count, position, \
+                             class:[a-z], forbid:abc,xyz or distinct:N",
+                        ),
+                )
+                .arg(clap::Arg::with_name("stream").long("stream").help(
+                    "only count entries following count and position, reading one \
+                             line of up to 64 KiB at a time so input of any size fits in memory",
+                ))
+                .arg(
+                    clap::Arg::with_name("input")
+                        .default_value("input/day2")
+                        .help("input file, or '-' to read stdin"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("toboggan")
+                .about("draw the path of a day 3 slope down the map, counting the trees it hits")
+                .arg(
+                    clap::Arg::with_name("right")
+                        .long("right")
+                        .default_value("3")
+                        .allow_hyphen_values(true)
+                        .help("columns to move right for each move down"),
+                )
+                .arg(
+                    clap::Arg::with_name("down")
+                        .long("down")
+                        .default_value("1")
+                        .help("rows to move down"),
+                )
+                .arg(
+                    clap::Arg::with_name("line")
+                        .long("line")
+                        .help("land on every row along the straight line of the slope"),
+                )
+                .arg(
+                    clap::Arg::with_name("search")
+                        .long("search")
+                        .help("find the slope hitting the fewest trees, up to --right and --down"),
+                )
+                .arg(
+                    clap::Arg::with_name("input")
+                        .default_value("input/day3")
+                        .help("input file, or '-' to read stdin"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("asm")
+                .about("assemble game console source, which may use labels and ';' comments")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .arg(extended_arg().global(true))
+                .subcommand(
+                    clap::SubCommand::with_name("check")
+                        .about("check that a source file assembles")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("list")
+                        .about("print the assembled code with addresses and jump targets")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("plain")
+                        .about("print the assembled code in the plain puzzle format")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("optimize")
+                        .about("print the optimized code in the plain puzzle format")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("compare")
+                        .about("run the code with and without optimizing, and check they agree")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("encode")
+                        .about("write the assembled code as bytecode to stdout")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("decode")
+                        .about("print bytecode in the plain puzzle format")
+                        .arg(asm_file_arg()),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("generate")
+                        .about("print a random program, with comments saying how to make it again")
+                        .arg(
+                            clap::Arg::with_name("len")
+                                .long("len")
+                                .default_value("100")
+                                .help("number of instructions"),
+                        )
+                        .arg(
+                            clap::Arg::with_name("loop_density")
+                                .long("loop-density")
+                                .default_value("0.3")
+                                .help("chance of a jmp or nop pointing backwards, from 0 to 1"),
+                        )
+                        .arg(
+                            clap::Arg::with_name("seed")
+                                .long("seed")
+                                .takes_value(true)
+                                .help("seed for the same program every time, defaults to random"),
+                        )
+                        .arg(clap::Arg::with_name("with_fix").long("with-fix").help(
+                            "make a program which loops unless one nop/jmp is swapped, like day 8",
+                        )),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("debug")
+                .about("step through a game console program, reading commands from stdin")
+                .after_help("Type 'help' at the prompt for the list of commands.")
+                .arg(extended_arg())
+                .arg(
+                    clap::Arg::with_name("file")
+                        .required(true)
+                        .help("program as bytecode, or in the plain or assembly format"),
+                ),
+        )
         .get_matches()
 }
+
+fn extended_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::with_name("extended")
+        .long("extended")
+        .help("also allow 'mul n' and 'jnz n', which jumps unless acc is zero")
+}
+
+fn asm_file_arg() -> clap::Arg<'static, 'static> {
+    clap::Arg::with_name("file")
+        .required(true)
+        .help("source file, or '-' to read stdin")
+}
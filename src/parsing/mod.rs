@@ -0,0 +1,179 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+/// An error in puzzle input, pointing at the offending text of a single line
+///
+/// ```text
+/// line 3, column 10: expected a number
+///   |
+/// 3 | mem[8] = 11x
+///   |          ^^^
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    line: usize,
+    text: String,
+    columns: Range<usize>,
+    expected: String,
+}
+
+/// Byte range of `part` within `text`, or all of `text` if `part` is not a slice of it
+fn span(text: &str, part: &str) -> Range<usize> {
+    let start = (part.as_ptr() as usize).wrapping_sub(text.as_ptr() as usize);
+    if start <= text.len() && part.len() <= text.len() - start {
+        start..start + part.len()
+    } else {
+        0..text.len()
+    }
+}
+
+impl ParseError {
+    /// An error covering the byte range `columns` of `text`, which is taken to be line 1
+    /// until `on_line` says otherwise
+    pub fn new<S: Into<String>>(text: &str, columns: Range<usize>, expected: S) -> ParseError {
+        let end = columns.end.min(text.len());
+        let start = columns.start.min(end);
+        ParseError {
+            line: 1,
+            text: text.to_string(),
+            columns: start..end,
+            expected: expected.into(),
+        }
+    }
+
+    /// An error covering `part`, which should be a slice of `text`
+    pub fn at<S: Into<String>>(text: &str, part: &str, expected: S) -> ParseError {
+        ParseError::new(text, span(text, part), expected)
+    }
+
+    /// An error just after `part`, for text which stopped short
+    pub fn after<S: Into<String>>(text: &str, part: &str, expected: S) -> ParseError {
+        let end = span(text, part).end;
+        ParseError::new(text, end..end, expected)
+    }
+
+    pub fn on_line(mut self, line: usize) -> ParseError {
+        self.line = line;
+        self
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// 1-based column range of the offending text, in characters
+    pub fn columns(&self) -> Range<usize> {
+        let start = self.text[..self.columns.start].chars().count() + 1;
+        let len = self.text[self.columns.clone()].chars().count();
+        start..start + len
+    }
+
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, columns) = (self.line(), self.columns());
+        let gutter = line.to_string().len();
+        writeln!(
+            f,
+            "line {}, column {}: expected {}",
+            line,
+            columns.start,
+            self.expected()
+        )?;
+        writeln!(f, "{:gutter$} |", "", gutter = gutter)?;
+        writeln!(f, "{} | {}", line, self.text)?;
+        write!(
+            f,
+            "{:gutter$} | {:pad$}{}",
+            "",
+            "",
+            "^".repeat((columns.end - columns.start).max(1)),
+            gutter = gutter,
+            pad = columns.start - 1
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse `part`, a slice of `text`, reporting `expected` at its position on failure
+pub fn parse_at<T: FromStr>(text: &str, part: &str, expected: &str) -> Result<T, ParseError> {
+    part.parse::<T>()
+        .map_err(|_| ParseError::at(text, part, expected))
+}
+
+/// Split `part`, a slice of `text`, around the first `pat`
+pub fn split_once<'a>(
+    text: &str,
+    part: &'a str,
+    pat: &str,
+) -> Result<(&'a str, &'a str), ParseError> {
+    part.split_once(pat)
+        .ok_or_else(|| ParseError::after(text, part, format!("{:?}", pat)))
+}
+
+/// Parse each line of `input`, numbering the line of any error
+pub fn parse_lines<'a, T, F>(input: &'a str, mut f: F) -> Result<Vec<T>, ParseError>
+where
+    F: FnMut(&'a str) -> Result<T, ParseError>,
+{
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, l)| f(l).map_err(|e| e.on_line(idx + 1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_caret() {
+        let line = "mem[8] = 11x";
+        let e = ParseError::at(line, &line[9..], "a number").on_line(3);
+        assert_eq!(e.columns(), 10..13);
+        assert_eq!(
+            e.to_string(),
+            "line 3, column 10: expected a number\n  |\n3 | mem[8] = 11x\n  |          ^^^"
+        );
+    }
+
+    #[test]
+    fn render_after() {
+        let line = "1-3 a";
+        let e = ParseError::after(line, line, "\":\"").on_line(12);
+        assert_eq!(e.columns(), 6..6);
+        assert_eq!(
+            e.to_string(),
+            "line 12, column 6: expected \":\"\n   |\n12 | 1-3 a\n   |      ^"
+        );
+    }
+
+    #[test]
+    fn span_of_unrelated_text() {
+        let e = ParseError::at("abc", "xyz", "something");
+        assert_eq!(e.columns(), 1..4);
+    }
+
+    #[test]
+    fn numbered_lines() {
+        let e = parse_lines("1\n2\nthree\n", |l| parse_at::<i64>(l, l, "a number")).unwrap_err();
+        assert_eq!(e.line(), 3);
+        assert_eq!(e.expected(), "a number");
+        assert_eq!(
+            parse_lines("1\n2\n", |l| parse_at::<i64>(l, l, "a number")).unwrap(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn missing_separator() {
+        let line = "abc def";
+        let e = split_once(line, &line[4..], ":").unwrap_err();
+        assert_eq!(e.columns(), 8..8);
+    }
+}